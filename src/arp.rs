@@ -0,0 +1,157 @@
+use ipnetwork::{IpNetwork, Ipv4Network};
+use pnet::datalink::{self, Channel, Config, MacAddr, NetworkInterface};
+use pnet::packet::arp::{ArpHardwareTypes, ArpOperations, ArpPacket, MutableArpPacket};
+use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
+use pnet::packet::{MutablePacket, Packet};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+use crate::error::PingError;
+use crate::link::DirectLink;
+use crate::pacer;
+use crate::socket::Binding;
+
+/// 以太网头 + ARP报文的长度
+const ARP_FRAME_LEN: usize = 42;
+
+/// 与目标网段直连的网卡，ARP请求从这里发出
+#[derive(Debug, Clone)]
+pub struct ArpInterface {
+    pub interface: NetworkInterface,
+    /// 网卡在该网段上的IPv4地址，作为ARP请求的发送方地址
+    pub source_ip: Ipv4Addr,
+    pub source_mac: MacAddr,
+    /// 网卡所在的网段，只有落在其中的地址才能用ARP探测
+    pub subnet: Ipv4Network,
+}
+
+impl ArpInterface {
+    /// 查找与`network`有交集的、已启用的非回环以太网卡
+    ///
+    /// 指定了网卡或源地址时，只使用该网卡或拥有该地址的网卡
+    pub fn for_network(network: &IpNetwork, binding: &Binding) -> Option<Self> {
+        if !network.is_ipv4() {
+            return None;
+        }
+        let link = DirectLink::for_network(network, binding)?;
+        match link.subnet {
+            IpNetwork::V4(subnet) => Some(Self {
+                interface: link.interface,
+                source_ip: subnet.ip(),
+                source_mac: link.source_mac,
+                subnet,
+            }),
+            IpNetwork::V6(_) => None,
+        }
+    }
+
+    /// 目标地址是否在网卡直连的网段中（不包括网卡自己的地址）
+    pub fn is_local(&self, ip: Ipv4Addr) -> bool {
+        self.subnet.contains(ip) && ip != self.source_ip
+    }
+}
+
+/// 对`targets`发送ARP请求，返回应答了的地址及其MAC
+///
+/// 阻塞调用，应放在`spawn_blocking`中执行；需要root或CAP_NET_RAW权限
+pub fn sweep(
+    arp: &ArpInterface,
+    targets: &[Ipv4Addr],
+    timeout: Duration,
+) -> Result<HashMap<Ipv4Addr, MacAddr>, PingError> {
+    let config = Config {
+        read_timeout: Some(Duration::from_millis(50)),
+        ..Default::default()
+    };
+    let (mut tx, mut rx) = match datalink::channel(&arp.interface, config) {
+        Ok(Channel::Ethernet(tx, rx)) => (tx, rx),
+        Ok(_) => return Err(PingError::Other("unsupported datalink channel".to_string())),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            return Err(PingError::PermissionDenied)
+        }
+        Err(e) => return Err(PingError::SendError(e)),
+    };
+
+    let mut frame = [0u8; ARP_FRAME_LEN];
+    for &target in targets {
+        build_request(&mut frame, arp.source_mac, arp.source_ip, target);
+        pacer::wait_blocking();
+        if let Some(Err(e)) = tx.send_to(&frame, None) {
+            return Err(PingError::SendError(e));
+        }
+    }
+
+    let wanted: HashSet<Ipv4Addr> = targets.iter().copied().collect();
+    let mut found = HashMap::new();
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline && found.len() < wanted.len() {
+        match rx.next() {
+            Ok(frame) => {
+                if let Some((ip, mac)) = parse_reply(frame) {
+                    if wanted.contains(&ip) {
+                        found.insert(ip, mac);
+                    }
+                }
+            }
+            // 读超时，继续等待直到截止时间
+            Err(_) => continue,
+        }
+    }
+
+    Ok(found)
+}
+
+/// 构造一个广播的ARP请求帧
+pub fn build_request(frame: &mut [u8], source_mac: MacAddr, source_ip: Ipv4Addr, target: Ipv4Addr) {
+    let mut ethernet = MutableEthernetPacket::new(frame).unwrap();
+    ethernet.set_destination(MacAddr::broadcast());
+    ethernet.set_source(source_mac);
+    ethernet.set_ethertype(EtherTypes::Arp);
+
+    let mut request = MutableArpPacket::new(ethernet.payload_mut()).unwrap();
+    request.set_hardware_type(ArpHardwareTypes::Ethernet);
+    request.set_protocol_type(EtherTypes::Ipv4);
+    request.set_hw_addr_len(6);
+    request.set_proto_addr_len(4);
+    request.set_operation(ArpOperations::Request);
+    request.set_sender_hw_addr(source_mac);
+    request.set_sender_proto_addr(source_ip);
+    request.set_target_hw_addr(MacAddr::zero());
+    request.set_target_proto_addr(target);
+}
+
+/// 解析ARP应答帧，返回应答方的(IP, MAC)
+pub fn parse_reply(frame: &[u8]) -> Option<(Ipv4Addr, MacAddr)> {
+    let ethernet = EthernetPacket::new(frame)?;
+    if ethernet.get_ethertype() != EtherTypes::Arp {
+        return None;
+    }
+    let reply = ArpPacket::new(ethernet.payload())?;
+    if reply.get_operation() != ArpOperations::Reply {
+        return None;
+    }
+    Some((reply.get_sender_proto_addr(), reply.get_sender_hw_addr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_reply_and_ignores_request() {
+        let mac = MacAddr::new(0x52, 0x54, 0x00, 0x12, 0x34, 0x56);
+        let ip = Ipv4Addr::new(192, 168, 1, 20);
+        let mut frame = [0u8; ARP_FRAME_LEN];
+
+        build_request(&mut frame, mac, ip, Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(parse_reply(&frame), None);
+
+        let mut ethernet = MutableEthernetPacket::new(&mut frame).unwrap();
+        let mut arp = MutableArpPacket::new(ethernet.payload_mut()).unwrap();
+        arp.set_operation(ArpOperations::Reply);
+        assert_eq!(parse_reply(&frame), Some((ip, mac)));
+        assert_eq!(mac.to_string(), "52:54:00:12:34:56");
+    }
+}
@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use std::net::IpAddr;
 
 #[derive(Parser)]
 #[command(author, version, about = "A fast ping utility implemented in Rust", long_about = None)]
@@ -11,10 +12,39 @@ pub struct Cli {
     #[arg(short, long)]
     pub file: Option<String>,
 
-    /// Number of pings to send to each target
+    /// Number of pings to send to each target (0 = until interrupted)
     #[arg(short = 'c', long, default_value = "3")]
     pub count: u32,
 
+    /// Loop mode: keep pinging until interrupted or the deadline passes (same as --count 0)
+    #[arg(short = 'l', long = "loop")]
+    pub loop_mode: bool,
+
+    /// Like fping -C: send N pings to each target and report every RTT sample in fping's format
+    #[arg(short = 'C', long, value_name = "N", conflicts_with = "loop_mode")]
+    pub vcount: Option<u32>,
+
+    /// Like fping -a: only print the targets that are alive
+    #[arg(short = 'a', long, conflicts_with = "unreach")]
+    pub alive: bool,
+
+    /// Like fping -u: only print the targets that are unreachable
+    #[arg(short = 'u', long)]
+    pub unreach: bool,
+
+    /// Count a target as unreachable when more than PERCENT of its probes fail after retries (0 = any loss);
+    /// by default a target is reachable if it answered at all
+    #[arg(long, value_name = "PERCENT")]
+    pub max_loss: Option<f64>,
+
+    /// Like fping -g: generate targets from a CIDR network or a start and end address
+    #[arg(short = 'g', long)]
+    pub generate: bool,
+
+    /// Stop after this many seconds, regardless of how many pings were sent
+    #[arg(long, value_name = "SECS")]
+    pub deadline: Option<u64>,
+
     /// Time between pings in milliseconds
     #[arg(short = 'p', long, default_value = "1000")]
     pub period: u64,
@@ -27,7 +57,19 @@ pub struct Cli {
     #[arg(short = 'r', long, default_value = "1")]
     pub retry: u32,
 
-    /// Size of the ICMP packet in bytes
+    /// Like fping -B: multiply the timeout and the delay before each retry by this factor
+    /// (ignored with --adaptive-timeout, which doubles the timeout itself)
+    #[arg(short = 'B', long, value_name = "FACTOR", default_value = "1.0")]
+    pub backoff: f64,
+
+    /// Derive each probe's timeout from the target's smoothed RTT and variance (RFC 6298),
+    /// doubling it after each timeout and using --timeout as the upper bound
+    #[arg(long)]
+    pub adaptive_timeout: bool,
+
+    /// Size of the ICMP packet in bytes (at least 24 for echo requests, to hold the
+    /// timestamp and nonce used to verify replies); with -a, -u or -C it is the data
+    /// size excluding the 8-byte ICMP header, like fping -b
     #[arg(short = 's', long, default_value = "56")]
     pub size: usize,
 
@@ -35,6 +77,10 @@ pub struct Cli {
     #[arg(short = 't', long, default_value = "64")]
     pub ttl: u32,
 
+    /// Comma-separated RTT percentiles to report in the summaries
+    #[arg(long, value_name = "LIST", default_value = "50,90,95,99,99.9")]
+    pub percentiles: String,
+
     /// Quiet mode - only show summary
     #[arg(short, long)]
     pub quiet: bool,
@@ -43,6 +89,43 @@ pub struct Cli {
     #[arg(short = 'T', long)]
     pub timestamp: bool,
 
+    /// Fill the payload with this hex pattern, up to 16 bytes (like ping -p)
+    #[arg(short = 'P', long)]
+    pub pattern: Option<String>,
+
+    /// Probe this TCP port with SYNs instead of sending ICMP echo requests
+    #[arg(long, value_name = "PORT")]
+    pub tcp: Option<u16>,
+
+    /// Send sequenced UDP datagrams to a `pingultra responder` on this port
+    #[arg(long, value_name = "PORT", conflicts_with = "tcp")]
+    pub udp: Option<u16>,
+
+    /// Send ICMP timestamp requests and estimate each host's clock offset (IPv4, needs root)
+    #[arg(long, conflicts_with_all = ["tcp", "udp"])]
+    pub icmp_timestamp: bool,
+
+    /// Send at most this many packets per second across all targets
+    #[arg(long, value_name = "PPS")]
+    pub rate: Option<u32>,
+
+    /// Minimum interval in milliseconds between any two packets, across all targets (like fping -i)
+    #[arg(short = 'i', long, value_name = "MS")]
+    pub interval: Option<u64>,
+
+    /// Mark probes with these DSCP values, comma separated (e.g. 0,ef or 0,46); each class is
+    /// measured separately. Append `@<DSCP>` to a host to override it per target
+    #[arg(long, value_name = "DSCP", conflicts_with_all = ["tcp", "udp"])]
+    pub dscp: Option<String>,
+
+    /// Send probes from this source address
+    #[arg(short = 'S', long)]
+    pub source: Option<IpAddr>,
+
+    /// Send probes out of this network interface (SO_BINDTODEVICE, Linux only)
+    #[arg(short = 'I', long)]
+    pub interface: Option<String>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -56,6 +139,95 @@ pub enum Commands {
         format: String,
     },
 
+    /// Trace the path to a host by sending probes with increasing TTL
+    Trace {
+        /// Target host (IP address or hostname)
+        host: String,
+
+        /// Maximum number of hops
+        #[arg(short = 'm', long, default_value = "30")]
+        max_hops: u32,
+
+        /// Number of probes per hop
+        #[arg(short = 'q', long, default_value = "3")]
+        queries: u32,
+
+        /// Timeout per probe in milliseconds
+        #[arg(short = 'w', long, default_value = "1000")]
+        timeout: u64,
+
+        /// Output format (text, json, csv)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+
+        /// Don't resolve hop addresses to hostnames
+        #[arg(short = 'n', long)]
+        numeric: bool,
+    },
+
+    /// Continuously probe every hop to a host and show per-hop loss and latency
+    Mtr {
+        /// Target host (IP address or hostname)
+        host: String,
+
+        /// Interval between probe cycles in milliseconds
+        #[arg(short = 'i', long, default_value = "1000")]
+        interval: u64,
+
+        /// Number of probe cycles (0 = until Ctrl+C)
+        #[arg(short = 'c', long, default_value = "0")]
+        cycles: u32,
+
+        /// Maximum number of hops
+        #[arg(short = 'm', long, default_value = "30")]
+        max_hops: u32,
+
+        /// Timeout per probe in milliseconds
+        #[arg(short = 'w', long, default_value = "1000")]
+        timeout: u64,
+
+        /// Output format of the final report (text, json, csv)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+
+        /// Don't resolve hop addresses to hostnames
+        #[arg(short = 'n', long)]
+        numeric: bool,
+
+        /// Report mode: don't refresh the terminal, only print the final report
+        #[arg(short = 'r', long)]
+        report: bool,
+    },
+
+    /// Discover the path MTU to a host by sending Don't Fragment probes of varying size
+    Pmtu {
+        /// Target host (IP address or hostname)
+        host: String,
+
+        /// Upper bound of the search, as an IP packet size in bytes
+        #[arg(short = 'M', long, default_value = "9000")]
+        max_mtu: usize,
+
+        /// Number of probes per size before treating it as dropped
+        #[arg(short = 'q', long, default_value = "2")]
+        queries: u32,
+
+        /// Timeout per probe in milliseconds
+        #[arg(short = 'w', long, default_value = "1000")]
+        timeout: u64,
+
+        /// Output format (text, json, csv)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Echo UDP probes back to a `pingultra --udp` sender
+    Responder {
+        /// UDP port to listen on
+        #[arg(short, long)]
+        port: u16,
+    },
+
     /// Monitor network for device changes (additions/removals)
     Monitor {
         /// IP range to scan (CIDR notation, e.g., 192.168.1.0/24)
@@ -81,5 +253,21 @@ pub enum Commands {
         /// Use character-based UI for monitoring
         #[arg(short = 'u', long)]
         ui: bool,
+
+        /// Send at most this many packets per second while scanning
+        #[arg(long, value_name = "PPS")]
+        rate: Option<u32>,
+
+        /// Minimum interval in milliseconds between any two scan packets
+        #[arg(long, value_name = "MS")]
+        packet_interval: Option<u64>,
+
+        /// Scan from this source address
+        #[arg(short = 'S', long)]
+        source: Option<IpAddr>,
+
+        /// Scan out of this network interface (Linux only)
+        #[arg(short = 'I', long)]
+        interface: Option<String>,
     },
 }
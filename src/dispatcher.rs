@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{Instant, SystemTime};
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::error::PingError;
+use crate::icmp::{
+    locate_icmpv4, parse_echo_reply, parse_echo_reply_v6, parse_error_reply, parse_error_reply_v6,
+    parse_timestamp_reply, IcmpReply, TimestampSource,
+};
+use crate::socket::{Family, IcmpSocket, Received, SocketKind, SocketOptions};
+use crate::tracker::SharedTracker;
+
+/// 用于把回复路由到等待中的探测：(标识符, 序号, 来源地址)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProbeKey {
+    pub identifier: u16,
+    pub sequence: u16,
+    pub addr: IpAddr,
+}
+
+impl ProbeKey {
+    pub fn new(identifier: u16, sequence: u16, addr: IpAddr) -> Self {
+        Self {
+            identifier,
+            sequence,
+            addr,
+        }
+    }
+}
+
+/// 回复分发器，持有一个共享的ICMP socket和一个接收任务，
+/// 接收任务把收到的回复按ProbeKey分发给等待中的探测，
+/// ICMP差错报文按其中携带的原始探测（标识符、序号、目的地址）分发
+///
+/// socket注册在tokio的reactor上，回复到达时接收任务立即被唤醒；
+/// 最后一个持有者释放分发器时停止接收任务并关闭socket
+pub struct ReplyDispatcher {
+    shared: Arc<Dispatcher>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+/// 分发器与接收任务共享的状态
+pub struct Dispatcher {
+    socket: AsyncFd<IcmpSocket>,
+    options: SocketOptions,
+    pending: Mutex<HashMap<ProbeKey, oneshot::Sender<IcmpReply>>>,
+    /// 按(标识符, 目标地址)登记的回复跟踪器，接收没有等待方的Echo Reply
+    trackers: Mutex<HashMap<(u16, IpAddr), SharedTracker>>,
+}
+
+/// 进程内共享的分发器，按socket配置索引；只保存弱引用，不会让分发器常驻
+static DISPATCHERS: OnceLock<Mutex<HashMap<SocketOptions, Weak<ReplyDispatcher>>>> =
+    OnceLock::new();
+
+impl ReplyDispatcher {
+    /// 获取与配置对应的共享分发器，不存在时创建socket并启动接收任务
+    ///
+    /// 必须在tokio运行时中调用
+    pub fn shared(options: SocketOptions) -> Result<Arc<Self>, PingError> {
+        let mut dispatchers = DISPATCHERS
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+            .unwrap();
+
+        if let Some(dispatcher) = dispatchers.get(&options).and_then(Weak::upgrade) {
+            return Ok(dispatcher);
+        }
+
+        let shared = Arc::new(Dispatcher {
+            socket: AsyncFd::new(IcmpSocket::open(&options)?)?,
+            options: options.clone(),
+            pending: Mutex::new(HashMap::new()),
+            trackers: Mutex::new(HashMap::new()),
+        });
+        let mut tasks = vec![tokio::spawn(shared.clone().receive_loop())];
+        #[cfg(target_os = "linux")]
+        if shared.socket.get_ref().kind() == SocketKind::Dgram {
+            tasks.push(tokio::spawn(shared.clone().error_queue_loop()));
+        }
+        let dispatcher = Arc::new(Self { shared, tasks });
+        dispatchers.insert(options, Arc::downgrade(&dispatcher));
+
+        Ok(dispatcher)
+    }
+}
+
+impl Deref for ReplyDispatcher {
+    type Target = Dispatcher;
+
+    fn deref(&self) -> &Dispatcher {
+        &self.shared
+    }
+}
+
+impl Drop for ReplyDispatcher {
+    fn drop(&mut self) {
+        // 接收任务持有共享状态，停止后socket随之关闭
+        for task in &self.tasks {
+            task.abort();
+        }
+        // 同一配置可能已经创建了新的分发器，只移除已失效的条目
+        if let Some(dispatchers) = DISPATCHERS.get() {
+            let mut dispatchers = dispatchers.lock().unwrap();
+            if dispatchers
+                .get(&self.options)
+                .is_some_and(|dispatcher| dispatcher.strong_count() == 0)
+            {
+                dispatchers.remove(&self.options);
+            }
+        }
+    }
+}
+
+impl Dispatcher {
+    /// 登记一个等待中的探测，返回用于接收回复的通道
+    ///
+    /// 应在发送之前调用，避免回复先于登记到达
+    pub fn register(&self, key: ProbeKey) -> oneshot::Receiver<IcmpReply> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(self.normalize(key), tx);
+        rx
+    }
+
+    /// 取消一个等待中的探测（超时或发送失败时调用）
+    pub fn cancel(&self, key: &ProbeKey) {
+        self.pending.lock().unwrap().remove(&self.normalize(*key));
+    }
+
+    /// 登记一个目标的回复跟踪器，迟到和重复的回复会交给它
+    ///
+    /// 数据报套接字无法用标识符区分探测，同一个分发器上同一地址只能有一个Pinger，
+    /// 否则两者的探测键相同，回复会被交给错误的等待方
+    pub fn track(
+        &self,
+        identifier: u16,
+        addr: IpAddr,
+        tracker: SharedTracker,
+    ) -> Result<(), PingError> {
+        let key = self.normalize(ProbeKey::new(identifier, 0, addr));
+        let mut trackers = self.trackers.lock().unwrap();
+        if trackers.contains_key(&(key.identifier, addr)) {
+            return Err(PingError::Other(format!(
+                "{} is already being probed with the same socket options",
+                addr
+            )));
+        }
+        trackers.insert((key.identifier, addr), tracker);
+        Ok(())
+    }
+
+    /// 移除回复跟踪器，只有仍是同一个跟踪器时才移除
+    pub fn untrack(&self, identifier: u16, addr: IpAddr, tracker: &SharedTracker) {
+        let key = self.normalize(ProbeKey::new(identifier, 0, addr));
+        let mut trackers = self.trackers.lock().unwrap();
+        if let Some(current) = trackers.get(&(key.identifier, addr)) {
+            if Arc::ptr_eq(current, tracker) {
+                trackers.remove(&(key.identifier, addr));
+            }
+        }
+    }
+
+    /// 数据报套接字的标识符会被内核改写为socket的端口号，
+    /// 此时只能依靠序号和来源地址区分探测
+    fn normalize(&self, key: ProbeKey) -> ProbeKey {
+        match self.socket.get_ref().kind() {
+            SocketKind::Raw => key,
+            SocketKind::Dgram => ProbeKey {
+                identifier: 0,
+                ..key
+            },
+        }
+    }
+
+    /// 共享socket的类型
+    pub fn socket_kind(&self) -> SocketKind {
+        self.socket.get_ref().kind()
+    }
+
+    /// 发送一个ICMP包，发送缓冲区满时等待socket可写
+    pub async fn send_to(&self, packet: &[u8], addr: IpAddr) -> std::io::Result<usize> {
+        self.socket
+            .async_io(Interest::WRITABLE, |socket| socket.send_to(packet, addr))
+            .await
+    }
+
+    async fn receive_loop(self: Arc<Self>) {
+        // 足够容纳最大的IP包，避免大包的回显数据被截断
+        let mut recv_buffer = vec![0u8; 65536];
+
+        loop {
+            let mut guard = match self.socket.readable().await {
+                Ok(guard) => guard,
+                // socket已从reactor中移除，无法继续接收
+                Err(_) => return,
+            };
+
+            match guard.try_io(|socket| socket.get_ref().recv(&mut recv_buffer)) {
+                Ok(Ok(received)) => {
+                    let (received_at, source) = receive_time(&received);
+                    self.dispatch(&recv_buffer[..received.len], &received, received_at, source);
+                }
+                // 单个包的接收错误不影响后续接收
+                Ok(Err(_)) => continue,
+                // 虚假唤醒，就绪状态已被清除，继续等待
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Linux的数据报套接字不会把ICMP差错报文交给recv，
+    /// 而是放进错误队列（IP_RECVERR），需要单独读取
+    #[cfg(target_os = "linux")]
+    async fn error_queue_loop(self: Arc<Self>) {
+        let mut recv_buffer = [0u8; 2048];
+
+        loop {
+            let mut guard = match self.socket.ready(Interest::ERROR).await {
+                Ok(guard) => guard,
+                Err(_) => return,
+            };
+
+            match guard.try_io(|socket| socket.get_ref().recv_error(&mut recv_buffer)) {
+                Ok(Ok(Some(error))) => {
+                    let key = ProbeKey::new(error.identifier, error.sequence, error.destination);
+                    self.deliver(key, IcmpReply::Error(error));
+                }
+                Ok(Ok(None)) | Ok(Err(_)) => continue,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn dispatch(
+        &self,
+        data: &[u8],
+        received: &Received,
+        received_at: Instant,
+        source: TimestampSource,
+    ) {
+        let reply = match self.options.family {
+            Family::V4 => {
+                let (offset, header_ttl) = match locate_icmpv4(data) {
+                    Some(location) => location,
+                    None => return,
+                };
+                let ttl = header_ttl.or(received.ttl).unwrap_or(0);
+                parse_echo_reply(data, offset, ttl, received_at)
+                    .map(IcmpReply::Echo)
+                    .or_else(|| {
+                        parse_timestamp_reply(data, offset, ttl, received_at)
+                            .map(IcmpReply::Timestamp)
+                    })
+                    .or_else(|| {
+                        parse_error_reply(data, offset, received.from, received_at)
+                            .map(IcmpReply::Error)
+                    })
+            }
+            Family::V6 => parse_echo_reply_v6(data, received.ttl.unwrap_or(0), received_at)
+                .map(IcmpReply::Echo)
+                .or_else(|| {
+                    parse_error_reply_v6(data, received.from, received_at).map(IcmpReply::Error)
+                }),
+        };
+
+        if let Some(mut reply) = reply {
+            if let IcmpReply::Echo(echo) = &mut reply {
+                echo.timestamp_source = source;
+            }
+            let key = match &reply {
+                IcmpReply::Echo(echo) => {
+                    ProbeKey::new(echo.identifier, echo.sequence, received.from)
+                }
+                IcmpReply::Timestamp(timestamp) => {
+                    ProbeKey::new(timestamp.identifier, timestamp.sequence, received.from)
+                }
+                IcmpReply::Error(error) => {
+                    ProbeKey::new(error.identifier, error.sequence, error.destination)
+                }
+            };
+            self.deliver(key, reply);
+        }
+    }
+
+    fn deliver(&self, key: ProbeKey, reply: IcmpReply) {
+        let key = self.normalize(key);
+        let tx = self.pending.lock().unwrap().remove(&key);
+        let tracker = self
+            .trackers
+            .lock()
+            .unwrap()
+            .get(&(key.identifier, key.addr))
+            .cloned();
+
+        match tx {
+            Some(tx) => {
+                // 先标记为已回复再交给等待方，等待方处理之前到达的同序号回复才会被识别为重复
+                if !matches!(reply, IcmpReply::Error(_)) {
+                    if let Some(tracker) = &tracker {
+                        tracker.lock().unwrap().delivered(key.sequence);
+                    }
+                }
+                // 等待方可能刚刚超时放弃，此时回复按迟到处理
+                if let (Err(IcmpReply::Echo(echo)), Some(tracker)) = (tx.send(reply), tracker) {
+                    tracker.lock().unwrap().abandoned(&echo);
+                }
+            }
+            // 没有等待方的Echo Reply是迟到或重复的回复
+            None => {
+                if let (IcmpReply::Echo(echo), Some(tracker)) = (reply, tracker) {
+                    tracker.lock().unwrap().unmatched(&echo);
+                }
+            }
+        }
+    }
+}
+
+/// 接收时间：优先使用内核时间戳，换算到Instant以便与发送时间相减
+///
+/// 没有内核时间戳（或时钟被回调）时使用当前时间，此时包含任务调度的延迟
+fn receive_time(received: &Received) -> (Instant, TimestampSource) {
+    let now = Instant::now();
+    let delay = received
+        .timestamp
+        .and_then(|timestamp| SystemTime::now().duration_since(timestamp).ok());
+
+    match delay.and_then(|delay| now.checked_sub(delay)) {
+        Some(received_at) => (received_at, TimestampSource::Kernel),
+        None => (now, TimestampSource::Userspace),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dispatcher_released_with_last_holder() {
+        let options = SocketOptions {
+            family: Family::V4,
+            // 与其他测试使用的配置不同，避免共享同一个分发器
+            ttl: 7,
+            dont_fragment: false,
+            binding: Default::default(),
+            dscp: 0,
+        };
+        // 没有ICMP socket权限时跳过
+        let dispatcher = match ReplyDispatcher::shared(options.clone()) {
+            Ok(dispatcher) => dispatcher,
+            Err(_) => return,
+        };
+        assert!(Arc::ptr_eq(
+            &dispatcher,
+            &ReplyDispatcher::shared(options.clone()).unwrap()
+        ));
+
+        let state = Arc::downgrade(&dispatcher.shared);
+        drop(dispatcher);
+        // 让被取消的接收任务有机会结束并释放共享状态
+        tokio::task::yield_now().await;
+
+        assert!(state.upgrade().is_none());
+        assert!(!DISPATCHERS
+            .get()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .contains_key(&options));
+    }
+}
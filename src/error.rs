@@ -1,6 +1,30 @@
 use std::io;
+use std::net::IpAddr;
 use thiserror::Error;
 
+use crate::icmp::IcmpErrorKind;
+
+/// 与fping相同的进程退出码，同时出现多种情况时取数值最大的一个
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExitStatus {
+    /// 所有目标都可达
+    AllReachable = 0,
+    /// 有目标不可达
+    Unreachable = 1,
+    /// 有主机名无法解析
+    UnresolvedHost = 2,
+    /// 命令行参数无效
+    InvalidArgument = 3,
+    /// 系统调用失败
+    SystemError = 4,
+}
+
+impl ExitStatus {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum PingError {
     #[error("Failed to send packet: {0}")]
@@ -15,9 +39,27 @@ pub enum PingError {
     #[error("Packet construction error")]
     PacketConstructionError,
 
-    #[error("Permission denied: raw sockets require root privileges")]
+    #[error("Permission denied: ICMP sockets require root privileges or ping_group_range")]
     PermissionDenied,
 
+    #[error("Destination net unreachable (from {0})")]
+    NetUnreachable(IpAddr),
+
+    #[error("Destination host unreachable (from {0})")]
+    HostUnreachable(IpAddr),
+
+    #[error("Destination port unreachable (from {0})")]
+    PortUnreachable(IpAddr),
+
+    #[error("Communication administratively prohibited (from {0})")]
+    AdminProhibited(IpAddr),
+
+    #[error("Time to live exceeded (from {0})")]
+    TtlExceeded(IpAddr),
+
+    #[error("Fragmentation needed, next-hop MTU {mtu} (from {router})")]
+    FragmentationNeeded { router: IpAddr, mtu: u32 },
+
     #[error("Failed to resolve hostname: {0}")]
     ResolutionError(String),
 
@@ -25,5 +67,33 @@ pub enum PingError {
     Other(String),
 }
 
+impl PingError {
+    /// 如果是由ICMP差错报文引起的错误，返回差错种类
+    pub fn icmp_error_kind(&self) -> Option<IcmpErrorKind> {
+        match self {
+            PingError::NetUnreachable(_) => Some(IcmpErrorKind::NetUnreachable),
+            PingError::HostUnreachable(_) => Some(IcmpErrorKind::HostUnreachable),
+            PingError::PortUnreachable(_) => Some(IcmpErrorKind::PortUnreachable),
+            PingError::AdminProhibited(_) => Some(IcmpErrorKind::AdminProhibited),
+            PingError::TtlExceeded(_) => Some(IcmpErrorKind::TtlExceeded),
+            PingError::FragmentationNeeded { .. } => Some(IcmpErrorKind::FragmentationNeeded),
+            _ => None,
+        }
+    }
+
+    /// 发送ICMP差错报文的路由器地址
+    pub fn router(&self) -> Option<IpAddr> {
+        match self {
+            PingError::NetUnreachable(router)
+            | PingError::HostUnreachable(router)
+            | PingError::PortUnreachable(router)
+            | PingError::AdminProhibited(router)
+            | PingError::TtlExceeded(router)
+            | PingError::FragmentationNeeded { router, .. } => Some(*router),
+            _ => None,
+        }
+    }
+}
+
 #[allow(dead_code)]
 pub type PingResult<T> = Result<T, PingError>;
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+/// 每个2的幂区间划分的子桶位数，相对误差不超过1/64
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKET_COUNT: u64 = 1 << SUB_BUCKET_BITS;
+const SUB_BUCKET_HALF: u64 = SUB_BUCKET_COUNT / 2;
+
+/// 按对数分桶的RTT直方图，用固定的内存流式计算分位数
+///
+/// 与HDR Histogram相同的分桶方式：以微秒为单位，小于128微秒的值精确记录，
+/// 更大的值在每个2的幂区间内再等分为64个子桶，因此误差与数值成比例
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistogram {
+    counts: Vec<u32>,
+    total: u64,
+    min: u64,
+    max: u64,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, rtt: Duration) {
+        let value = rtt.as_micros().min(u64::MAX as u128) as u64;
+        let index = bucket_index(value);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+
+        if self.total == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.total += 1;
+    }
+
+    /// 第`percentile`百分位的RTT，取所在桶的中点并限制在已记录的最小值和最大值之间；
+    /// 落在最后一个样本时直接返回记录的最大值
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.total == 0 {
+            return None;
+        }
+        let rank = ((percentile / 100.0 * self.total as f64).ceil() as u64).clamp(1, self.total);
+        if rank == self.total {
+            return Some(Duration::from_micros(self.max));
+        }
+
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += *count as u64;
+            if seen >= rank {
+                let (low, high) = bucket_range(index);
+                let value = (low + (high - low) / 2).clamp(self.min, self.max);
+                return Some(Duration::from_micros(value));
+            }
+        }
+        None
+    }
+}
+
+/// 值所在的桶：第0组为0..128的精确值，第b组（b≥1）以2^b为宽度覆盖64个子桶
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKET_COUNT {
+        return value as usize;
+    }
+    let magnitude = 63 - value.leading_zeros();
+    let shift = magnitude + 1 - SUB_BUCKET_BITS;
+    (shift as u64 * SUB_BUCKET_HALF + (value >> shift)) as usize
+}
+
+/// 桶覆盖的取值范围（闭区间）
+fn bucket_range(index: usize) -> (u64, u64) {
+    let index = index as u64;
+    if index < SUB_BUCKET_COUNT {
+        return (index, index);
+    }
+    let shift = index / SUB_BUCKET_HALF - 1;
+    let sub_bucket = index - shift * SUB_BUCKET_HALF;
+    let low = sub_bucket << shift;
+    (low, low + (1 << shift) - 1)
+}
+
+/// 解析逗号分隔的百分位列表，例如`50,90,99.9`
+pub fn parse_percentiles(spec: &str) -> Result<Vec<f64>, String> {
+    spec.split(',')
+        .map(|part| {
+            let part = part.trim();
+            match part.parse::<f64>() {
+                Ok(p) if p > 0.0 && p <= 100.0 => Ok(p),
+                _ => Err(format!(
+                    "Invalid percentile '{}': expected a number in (0, 100]",
+                    part
+                )),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_cover_values_without_gaps() {
+        for value in [0, 127, 128, 129, 255, 256, 1000, 123_456, u32::MAX as u64] {
+            let (low, high) = bucket_range(bucket_index(value));
+            assert!(
+                low <= value && value <= high,
+                "{}: {}..{}",
+                value,
+                low,
+                high
+            );
+            assert!((high - low) as f64 <= value as f64 / 64.0);
+        }
+        assert_eq!(bucket_index(127) + 1, bucket_index(128));
+        assert_eq!(bucket_range(bucket_index(255)).1 + 1, 256);
+    }
+
+    #[test]
+    fn test_percentiles_within_bucket_precision() {
+        let mut histogram = LatencyHistogram::new();
+        assert_eq!(histogram.percentile(50.0), None);
+
+        // 1ms到1000ms各一个样本
+        for ms in 1..=1000 {
+            histogram.record(Duration::from_millis(ms));
+        }
+        for (percentile, expected_ms) in [(50.0, 500.0), (90.0, 900.0), (99.9, 999.0)] {
+            let actual = histogram.percentile(percentile).unwrap().as_secs_f64() * 1000.0;
+            assert!(
+                (actual - expected_ms).abs() <= expected_ms / 64.0,
+                "p{}: {}",
+                percentile,
+                actual
+            );
+        }
+        assert_eq!(
+            histogram.percentile(100.0),
+            Some(Duration::from_millis(1000))
+        );
+
+        assert_eq!(parse_percentiles("50, 99.9").unwrap(), [50.0, 99.9]);
+        assert!(parse_percentiles("0").is_err());
+        assert!(parse_percentiles("p99").is_err());
+    }
+}
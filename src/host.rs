@@ -1,18 +1,65 @@
 use anyhow::Result;
-use std::net::{IpAddr, ToSocketAddrs};
+use ipnetwork::IpNetwork;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs};
 use std::time::Duration;
 
 use crate::error::PingError;
+use crate::icmp::{ClockEstimate, PayloadCheck, TimestampSource};
+use crate::socket::parse_dscp_list;
+use crate::tracker::ReplyClass;
 
 #[derive(Debug, Clone)]
 pub struct PingTarget {
     pub name: String,
     pub addr: IpAddr,
+    /// 探测使用的DSCP，同一主机的不同DSCP分别统计
+    pub dscp: Option<u8>,
 }
 
 impl PingTarget {
     pub fn new(host: String, addr: IpAddr) -> Self {
-        Self { name: host, addr }
+        Self {
+            name: host,
+            addr,
+            dscp: None,
+        }
+    }
+
+    pub fn with_dscp(mut self, dscp: Option<u8>) -> Self {
+        self.dscp = dscp;
+        self
+    }
+
+    /// 统计和摘要中使用的名称，指定了DSCP时带上DSCP以区分不同的服务类别
+    pub fn label(&self) -> String {
+        match self.dscp {
+            Some(dscp) => format!("{} [dscp {}]", self.name, dscp),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// 响应对应的探测方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    /// ICMP回显请求
+    Icmp,
+    /// TCP SYN，`open`为false表示收到RST：主机在线但端口关闭
+    Tcp { port: u16, open: bool },
+    /// 发往`pingultra responder`的UDP数据报
+    Udp { port: u16 },
+    /// ICMP时间戳请求，`clock`为对端时钟的估算，对端不使用标准时间时为None
+    Timestamp { clock: Option<ClockEstimate> },
+}
+
+impl Probe {
+    /// 输出中序号的名称
+    pub fn seq_label(&self) -> &'static str {
+        match self {
+            Probe::Icmp | Probe::Timestamp { .. } => "icmp_seq",
+            Probe::Tcp { .. } => "tcp_seq",
+            Probe::Udp { .. } => "udp_seq",
+        }
     }
 }
 
@@ -24,6 +71,23 @@ pub struct PingResponse {
     pub bytes: usize,
     pub ttl: u8,
     pub error: Option<PingError>,
+    /// 回显数据的校验结果，只对成功的响应有意义
+    pub payload_check: PayloadCheck,
+    /// 回复是按时、乱序、迟到还是重复到达的
+    pub class: ReplyClass,
+    /// RTT的接收时间来源
+    pub timestamp_source: TimestampSource,
+    /// 探测方式
+    pub probe: Probe,
+    /// 探测使用的源地址，多出口时用于区分结果来自哪条链路
+    pub source: Option<IpAddr>,
+    /// 所属逻辑探测的编号，一个探测及其全部重试共用一个编号；`seq`是每次发送在线路上的序号。
+    /// 分发器报告的迟到和重复回复无法对应到逻辑探测，为None
+    pub probe_seq: Option<u64>,
+    /// 同一个逻辑探测的第几次发送，0为第一次，大于0为重试
+    pub attempt: u32,
+    /// 这次发送是否结束了所属的逻辑探测（收到回复或重试已用尽）
+    pub final_attempt: bool,
 }
 
 impl PingResponse {
@@ -35,6 +99,41 @@ impl PingResponse {
             bytes,
             ttl,
             error: None,
+            payload_check: PayloadCheck::Intact,
+            class: ReplyClass::OnTime,
+            timestamp_source: TimestampSource::Userspace,
+            probe: Probe::Icmp,
+            source: None,
+            probe_seq: None,
+            attempt: 0,
+            final_attempt: true,
+        }
+    }
+
+    /// 收到ICMP差错报文的失败响应，保留差错报文到达的时间
+    pub fn icmp_error(
+        target: PingTarget,
+        seq: u16,
+        rtt: Duration,
+        bytes: usize,
+        ttl: u8,
+        error: PingError,
+    ) -> Self {
+        Self {
+            target,
+            seq,
+            rtt: Some(rtt),
+            bytes,
+            ttl,
+            error: Some(error),
+            payload_check: PayloadCheck::Intact,
+            class: ReplyClass::OnTime,
+            timestamp_source: TimestampSource::Userspace,
+            probe: Probe::Icmp,
+            source: None,
+            probe_seq: None,
+            attempt: 0,
+            final_attempt: true,
         }
     }
 
@@ -46,12 +145,38 @@ impl PingResponse {
             bytes,
             ttl,
             error: Some(error),
+            payload_check: PayloadCheck::Intact,
+            class: ReplyClass::OnTime,
+            timestamp_source: TimestampSource::Userspace,
+            probe: Probe::Icmp,
+            source: None,
+            probe_seq: None,
+            attempt: 0,
+            final_attempt: true,
         }
     }
 
     pub fn is_success(&self) -> bool {
         self.error.is_none()
     }
+
+    /// 应答者的地址：成功时是目标本身，ICMP差错时是发送差错的路由器
+    pub fn responder(&self) -> Option<IpAddr> {
+        match &self.error {
+            None => Some(self.target.addr),
+            Some(e) => e.router(),
+        }
+    }
+}
+
+/// 拆分目标参数中的DSCP后缀，例如`host@ef`或`host@0,46`
+///
+/// 没有后缀时返回None，此时使用命令行的`--dscp`
+pub fn split_target_dscp(spec: &str) -> Result<(String, Option<Vec<u8>>), String> {
+    match spec.rsplit_once('@') {
+        Some((host, classes)) => Ok((host.to_string(), Some(parse_dscp_list(classes)?))),
+        None => Ok((spec.to_string(), None)),
+    }
 }
 
 pub fn resolve_host(host: &str) -> Result<IpAddr, PingError> {
@@ -100,3 +225,140 @@ pub fn load_hosts_from_file(file_path: &str) -> Result<Vec<String>> {
 
     Ok(hosts)
 }
+
+/// fping -g最多展开的地址数，与fping的限制相同
+const MAX_GENERATE: u128 = 131_072;
+
+/// 与fping -g相同，把一个网段（`192.168.1.0/24`）或一对起止地址（`192.168.1.1 192.168.1.20`）展开为目标列表
+///
+/// IPv4网段（前缀短于/31）不包括网络地址和广播地址
+pub fn generate_targets(args: &[String]) -> Result<Vec<String>, String> {
+    let (first, last) = match args {
+        [network] => {
+            let network: IpNetwork = network
+                .parse()
+                .map_err(|_| format!("invalid network: {}", network))?;
+            let host_bits = match network {
+                IpNetwork::V4(network) => 32 - network.prefix() as u32,
+                IpNetwork::V6(network) => 128 - network.prefix() as u32,
+            };
+            let first = network.network();
+            let size = 1u128.checked_shl(host_bits).unwrap_or(0);
+            let last = ip_from_u128(first, ip_to_u128(first) + size.wrapping_sub(1));
+            match network {
+                IpNetwork::V4(_) if host_bits > 1 => (
+                    ip_from_u128(first, ip_to_u128(first) + 1),
+                    ip_from_u128(first, ip_to_u128(last) - 1),
+                ),
+                _ => (first, last),
+            }
+        }
+        [start, end] => {
+            let parse = |addr: &String| {
+                addr.parse::<IpAddr>()
+                    .map_err(|_| format!("invalid address: {}", addr))
+            };
+            let (start, end) = (parse(start)?, parse(end)?);
+            if start.is_ipv4() != end.is_ipv4() || start > end {
+                return Err(format!("invalid address range: {} - {}", start, end));
+            }
+            (start, end)
+        }
+        _ => {
+            return Err(
+                "-g needs a network (e.g. 192.168.1.0/24) or a start and end address".to_string(),
+            )
+        }
+    };
+
+    let (first_value, last_value) = (ip_to_u128(first), ip_to_u128(last));
+    if last_value - first_value >= MAX_GENERATE {
+        return Err(format!(
+            "-g would generate more than {} targets",
+            MAX_GENERATE
+        ));
+    }
+    Ok((first_value..=last_value)
+        .map(|value| ip_from_u128(first, value).to_string())
+        .collect())
+}
+
+fn ip_to_u128(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(addr) => u32::from(addr) as u128,
+        IpAddr::V6(addr) => u128::from(addr),
+    }
+}
+
+/// 按`family`的地址族把整数转换回地址
+fn ip_from_u128(family: IpAddr, value: u128) -> IpAddr {
+    match family {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::from(value as u32)),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::from(value)),
+    }
+}
+
+/// 反向DNS查询，获取IP地址对应的主机名
+pub async fn resolve_hostname(ip: IpAddr) -> Option<String> {
+    // 使用反向DNS查询获取主机名
+    match tokio::process::Command::new("host")
+        .arg(ip.to_string())
+        .output()
+        .await
+    {
+        Ok(output) => {
+            if output.status.success() {
+                let output_str = String::from_utf8_lossy(&output.stdout);
+
+                // 解析host命令输出，提取主机名
+                // 格式通常是: IP地址 domain name pointer hostname.
+                for line in output_str.lines() {
+                    if line.contains("domain name pointer") {
+                        let parts: Vec<&str> = line.split("domain name pointer").collect();
+                        if parts.len() >= 2 {
+                            let hostname = parts[1].trim().trim_end_matches('.');
+                            return Some(hostname.to_string());
+                        }
+                    }
+                }
+            }
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(args: &[&str]) -> Result<Vec<String>, String> {
+        generate_targets(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_generate_targets_like_fping() {
+        // 不包括网络地址和广播地址
+        assert_eq!(
+            generate(&["192.168.1.0/30"]).unwrap(),
+            ["192.168.1.1", "192.168.1.2"]
+        );
+        assert_eq!(
+            generate(&["10.0.0.0/31"]).unwrap(),
+            ["10.0.0.0", "10.0.0.1"]
+        );
+        assert_eq!(
+            generate(&["10.0.0.254", "10.0.1.1"]).unwrap(),
+            ["10.0.0.254", "10.0.0.255", "10.0.1.0", "10.0.1.1"]
+        );
+        assert_eq!(
+            generate(&["2001:db8::/127"]).unwrap(),
+            ["2001:db8::", "2001:db8::1"]
+        );
+
+        assert!(generate(&["10.0.0.0/8"]).is_err());
+        assert!(generate(&["::/0"]).is_err());
+        assert!(generate(&["10.0.0.2", "10.0.0.1"]).is_err());
+        assert!(generate(&["10.0.0.1", "::1"]).is_err());
+    }
+}
@@ -1,25 +1,300 @@
 use pnet::packet::icmp::echo_request::{EchoRequestPacket, MutableEchoRequestPacket};
 use pnet::packet::icmp::{IcmpPacket, IcmpTypes};
+use pnet::packet::icmpv6::echo_request::{
+    EchoRequestPacket as EchoRequestV6Packet,
+    MutableEchoRequestPacket as MutableEchoRequestV6Packet,
+};
+use pnet::packet::icmpv6::{Icmpv6Code, Icmpv6Packet, Icmpv6Types};
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4::Ipv4Packet;
 use pnet::packet::Packet;
-use std::time::{Duration, Instant};
+use rand::random;
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use crate::error::PingError;
 
+/// 数据部分开头的探测头：8字节发送时间戳（Unix纳秒）+ 8字节随机数
+pub const PAYLOAD_HEADER_LEN: usize = 16;
+/// ICMP Echo头长度，ICMPv4和ICMPv6相同
+pub const ICMP_HEADER_LEN: usize = 8;
+/// 能容纳探测头的最小ICMP包大小（Echo头 + 探测头），更小的包无法校验应答
+pub const MIN_ECHO_SIZE: usize = ICMP_HEADER_LEN + PAYLOAD_HEADER_LEN;
+
 pub struct IcmpEchoRequest {
     pub identifier: u16,
     #[allow(dead_code)]
     pub sequence: u16,
     pub payload_size: usize,
+    /// 每个探测独有的随机数，用于确认回复确实回显了本次探测的数据
+    pub nonce: u64,
+    /// 用户指定的填充模式（类似ping -p），为None时使用递增字节
+    pub pattern: Option<Vec<u8>>,
 }
 
 pub struct IcmpEchoReply {
-    #[allow(dead_code)]
     pub identifier: u16,
-    #[allow(dead_code)]
     pub sequence: u16,
     pub ttl: u8,
     pub size: usize,
-    pub rtt: Duration,
+    /// ICMP头之后回显的数据
+    pub payload: Vec<u8>,
+    pub received_at: Instant,
+    /// received_at来自内核时间戳还是用户态计时
+    pub timestamp_source: TimestampSource,
+}
+
+/// ICMP时间戳请求/应答（类型13/14）的长度：8字节头 + 发起、接收、发送三个时间戳
+pub const TIMESTAMP_PACKET_LEN: usize = 20;
+
+/// 一天的毫秒数，时间戳在UTC午夜回绕
+const MS_PER_DAY: i64 = 86_400_000;
+
+/// ICMP时间戳应答，时间戳均为距UTC午夜的毫秒数（RFC 792）
+pub struct IcmpTimestampReply {
+    pub identifier: u16,
+    pub sequence: u16,
+    pub ttl: u8,
+    /// 我们发送请求的时间
+    pub originate: u32,
+    /// 对端收到请求的时间
+    pub receive: u32,
+    /// 对端发送应答的时间
+    pub transmit: u32,
+    pub received_at: Instant,
+}
+
+/// 由一次时间戳交换估算的单向时延，对端时钟偏差为二者之差的一半
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockEstimate {
+    /// 去程时延（对端接收时间 - 发起时间），包含时钟偏差
+    pub forward_ms: i32,
+    /// 回程时延（我们收到应答的时间 - 对端发送时间），包含时钟偏差
+    pub return_ms: i32,
+}
+
+impl ClockEstimate {
+    /// 对端时钟相对本机的偏差，正值表示对端时钟偏快
+    pub fn offset_ms(&self) -> f64 {
+        (self.forward_ms - self.return_ms) as f64 / 2.0
+    }
+}
+
+impl IcmpTimestampReply {
+    /// 以我们收到应答的时间`arrival`（距UTC午夜的毫秒数）估算时延和时钟偏差
+    ///
+    /// 对端置位最高位表示时间戳不是标准的UTC毫秒数，此时无法估算
+    pub fn estimate(&self, arrival: u32) -> Option<ClockEstimate> {
+        if self.receive & 0x8000_0000 != 0 || self.transmit & 0x8000_0000 != 0 {
+            return None;
+        }
+        Some(ClockEstimate {
+            forward_ms: ms_diff(self.receive, self.originate),
+            return_ms: ms_diff(arrival, self.transmit),
+        })
+    }
+}
+
+/// 距UTC午夜的毫秒数
+pub fn ms_since_midnight(time: SystemTime) -> u32 {
+    let ms = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64);
+    ms.rem_euclid(MS_PER_DAY) as u32
+}
+
+/// 两个时间戳之差，跨越午夜时按最近的方向计算
+fn ms_diff(a: u32, b: u32) -> i32 {
+    let diff = (a as i64 - b as i64).rem_euclid(MS_PER_DAY);
+    if diff > MS_PER_DAY / 2 {
+        (diff - MS_PER_DAY) as i32
+    } else {
+        diff as i32
+    }
+}
+
+/// 接收时间的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampSource {
+    /// 内核在收包时记录的时间（SO_TIMESTAMPNS）
+    Kernel,
+    /// 接收任务被唤醒后读取的时间，包含调度延迟
+    Userspace,
+}
+
+impl TimestampSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimestampSource::Kernel => "kernel",
+            TimestampSource::Userspace => "userspace",
+        }
+    }
+}
+
+/// 回显数据的校验结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadCheck {
+    Intact,
+    /// 回复比发出的包短
+    Truncated,
+    /// 回复的数据与发出的不一致
+    Corrupted,
+}
+
+/// 比较回显的数据和发出的数据
+pub fn verify_payload(sent: &[u8], received: &[u8]) -> PayloadCheck {
+    if received.len() < sent.len() {
+        PayloadCheck::Truncated
+    } else if &received[..sent.len()] != sent {
+        PayloadCheck::Corrupted
+    } else {
+        PayloadCheck::Intact
+    }
+}
+
+/// 解析填充模式，与ping -p相同，最多16字节的十六进制数
+pub fn parse_pattern(hex: &str) -> Result<Vec<u8>, String> {
+    let invalid = || format!("invalid pattern: {}", hex);
+    if hex.is_empty() || hex.len() > 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    // 奇数个字符时在前面补0
+    let padded = if hex.len() % 2 == 1 {
+        format!("0{}", hex)
+    } else {
+        hex.to_string()
+    };
+    (0..padded.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&padded[i..i + 2], 16).map_err(|_| invalid()))
+        .collect()
+}
+
+/// ICMP差错报文的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IcmpErrorKind {
+    NetUnreachable,
+    HostUnreachable,
+    PortUnreachable,
+    AdminProhibited,
+    TtlExceeded,
+    FragmentationNeeded,
+}
+
+impl IcmpErrorKind {
+    pub const ALL: [IcmpErrorKind; 6] = [
+        IcmpErrorKind::NetUnreachable,
+        IcmpErrorKind::HostUnreachable,
+        IcmpErrorKind::PortUnreachable,
+        IcmpErrorKind::AdminProhibited,
+        IcmpErrorKind::TtlExceeded,
+        IcmpErrorKind::FragmentationNeeded,
+    ];
+
+    /// 用于JSON/CSV输出的名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            IcmpErrorKind::NetUnreachable => "net_unreachable",
+            IcmpErrorKind::HostUnreachable => "host_unreachable",
+            IcmpErrorKind::PortUnreachable => "port_unreachable",
+            IcmpErrorKind::AdminProhibited => "admin_prohibited",
+            IcmpErrorKind::TtlExceeded => "ttl_exceeded",
+            IcmpErrorKind::FragmentationNeeded => "frag_needed",
+        }
+    }
+
+    /// 类似iputils ping的可读描述
+    pub fn description(&self) -> &'static str {
+        match self {
+            IcmpErrorKind::NetUnreachable => "Destination Net Unreachable",
+            IcmpErrorKind::HostUnreachable => "Destination Host Unreachable",
+            IcmpErrorKind::PortUnreachable => "Destination Port Unreachable",
+            IcmpErrorKind::AdminProhibited => "Communication Administratively Prohibited",
+            IcmpErrorKind::TtlExceeded => "Time to live exceeded",
+            IcmpErrorKind::FragmentationNeeded => "Frag needed and DF set",
+        }
+    }
+
+    /// fping报告ICMP差错时使用的描述
+    pub fn fping_description(&self) -> &'static str {
+        match self {
+            IcmpErrorKind::NetUnreachable => "ICMP Network Unreachable",
+            IcmpErrorKind::HostUnreachable => "ICMP Host Unreachable",
+            IcmpErrorKind::PortUnreachable => "ICMP Port Unreachable",
+            IcmpErrorKind::AdminProhibited => {
+                "ICMP Unreachable (Communication Administratively Prohibited)"
+            }
+            IcmpErrorKind::TtlExceeded => "ICMP Time Exceeded",
+            IcmpErrorKind::FragmentationNeeded => "ICMP Unreachable (Fragmentation Needed)",
+        }
+    }
+
+    /// 由ICMPv4的类型和代码得到差错种类，重定向等非差错报文返回None
+    ///
+    /// 重定向只表示存在更优的网关，原始包仍会被转发，因此不作为探测失败
+    pub fn from_icmpv4(icmp_type: u8, code: u8) -> Option<Self> {
+        match (icmp_type, code) {
+            (3, 0 | 5 | 6 | 11) => Some(IcmpErrorKind::NetUnreachable),
+            (3, 1 | 7 | 8 | 12) => Some(IcmpErrorKind::HostUnreachable),
+            (3, 2 | 3) => Some(IcmpErrorKind::PortUnreachable),
+            (3, 4) => Some(IcmpErrorKind::FragmentationNeeded),
+            (3, 9 | 10 | 13..=15) => Some(IcmpErrorKind::AdminProhibited),
+            (11, _) => Some(IcmpErrorKind::TtlExceeded),
+            _ => None,
+        }
+    }
+
+    /// 由ICMPv6的类型和代码得到差错种类
+    pub fn from_icmpv6(icmp_type: u8, code: u8) -> Option<Self> {
+        match (icmp_type, code) {
+            (1, 0 | 2) => Some(IcmpErrorKind::NetUnreachable),
+            (1, 3) => Some(IcmpErrorKind::HostUnreachable),
+            (1, 4) => Some(IcmpErrorKind::PortUnreachable),
+            (1, 1 | 5 | 6) => Some(IcmpErrorKind::AdminProhibited),
+            (2, _) => Some(IcmpErrorKind::FragmentationNeeded),
+            (3, _) => Some(IcmpErrorKind::TtlExceeded),
+            _ => None,
+        }
+    }
+}
+
+/// 从ICMP差错报文中还原出的原始探测
+pub struct IcmpErrorReply {
+    pub kind: IcmpErrorKind,
+    /// 发送差错报文的路由器
+    pub router: IpAddr,
+    /// 下一跳MTU，只在分片差错（Packet Too Big）中有效
+    pub mtu: Option<u32>,
+    pub identifier: u16,
+    pub sequence: u16,
+    /// 原始探测的目的地址
+    pub destination: IpAddr,
+    pub received_at: Instant,
+}
+
+impl IcmpErrorReply {
+    pub fn to_error(&self) -> PingError {
+        let router = self.router;
+        match self.kind {
+            IcmpErrorKind::NetUnreachable => PingError::NetUnreachable(router),
+            IcmpErrorKind::HostUnreachable => PingError::HostUnreachable(router),
+            IcmpErrorKind::PortUnreachable => PingError::PortUnreachable(router),
+            IcmpErrorKind::AdminProhibited => PingError::AdminProhibited(router),
+            IcmpErrorKind::TtlExceeded => PingError::TtlExceeded(router),
+            IcmpErrorKind::FragmentationNeeded => PingError::FragmentationNeeded {
+                router,
+                mtu: self.mtu.unwrap_or(0),
+            },
+        }
+    }
+}
+
+/// 分发器收到的、属于某个探测的ICMP报文
+pub enum IcmpReply {
+    Echo(IcmpEchoReply),
+    Timestamp(IcmpTimestampReply),
+    Error(IcmpErrorReply),
 }
 
 impl IcmpEchoRequest {
@@ -28,22 +303,49 @@ impl IcmpEchoRequest {
             identifier,
             sequence,
             payload_size,
+            nonce: random::<u64>(),
+            pattern: None,
         }
     }
 
-    pub fn create_packet<'a>(
-        &self,
-        buffer: &'a mut [u8],
-    ) -> Result<MutableEchoRequestPacket<'a>, PingError> {
-        // 首先填充payload
+    pub fn with_pattern(mut self, pattern: Vec<u8>) -> Self {
+        if !pattern.is_empty() {
+            self.pattern = Some(pattern);
+        }
+        self
+    }
+
+    /// 填充数据部分：空间足够时先写入发送时间戳和随机数，其余按填充模式填充
+    fn fill_payload(&self, buffer: &mut [u8]) {
+        // ICMPv4和ICMPv6的Echo头长度相同，都是8字节
         let payload_offset = EchoRequestPacket::minimum_packet_size();
         let payload_size = self.payload_size.saturating_sub(payload_offset);
 
         if payload_size > 0 && buffer.len() >= payload_offset + payload_size {
-            for i in 0..payload_size {
-                buffer[payload_offset + i] = (i % 256) as u8;
+            let payload = &mut buffer[payload_offset..payload_offset + payload_size];
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte = match &self.pattern {
+                    Some(pattern) => pattern[i % pattern.len()],
+                    None => (i % 256) as u8,
+                };
+            }
+
+            if payload_size >= PAYLOAD_HEADER_LEN {
+                let sent_at = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_nanos() as u64);
+                payload[..8].copy_from_slice(&sent_at.to_be_bytes());
+                payload[8..16].copy_from_slice(&self.nonce.to_be_bytes());
             }
         }
+    }
+
+    pub fn create_packet<'a>(
+        &self,
+        buffer: &'a mut [u8],
+    ) -> Result<MutableEchoRequestPacket<'a>, PingError> {
+        // 首先填充payload
+        self.fill_payload(buffer);
 
         // 然后创建packet
         let mut packet =
@@ -60,15 +362,107 @@ impl IcmpEchoRequest {
 
         Ok(packet)
     }
+
+    /// 构造ICMPv6 Echo Request（类型128），校验和包含IPv6伪首部
+    pub fn create_packet_v6<'a>(
+        &self,
+        buffer: &'a mut [u8],
+        source: Ipv6Addr,
+        destination: Ipv6Addr,
+    ) -> Result<MutableEchoRequestV6Packet<'a>, PingError> {
+        self.fill_payload(buffer);
+
+        let mut packet =
+            MutableEchoRequestV6Packet::new(buffer).ok_or(PingError::PacketConstructionError)?;
+
+        packet.set_icmpv6_type(Icmpv6Types::EchoRequest);
+        packet.set_icmpv6_code(Icmpv6Code::new(0));
+        packet.set_sequence_number(self.sequence);
+        packet.set_identifier(self.identifier);
+
+        // 内核通常会重新计算ICMPv6校验和，这里仍按RFC 4443计算以防万一
+        let checksum = pnet::packet::icmpv6::checksum(
+            &Icmpv6Packet::new(packet.packet()).unwrap(),
+            &source,
+            &destination,
+        );
+        packet.set_checksum(checksum);
+
+        Ok(packet)
+    }
+}
+
+/// 构造ICMP时间戳请求（类型13），发起时间戳取当前时间
+pub fn create_timestamp_request(
+    buffer: &mut [u8],
+    identifier: u16,
+    sequence: u16,
+) -> Result<(), PingError> {
+    let packet = buffer
+        .get_mut(..TIMESTAMP_PACKET_LEN)
+        .ok_or(PingError::PacketConstructionError)?;
+    packet.fill(0);
+    packet[0] = IcmpTypes::Timestamp.0;
+    packet[4..6].copy_from_slice(&identifier.to_be_bytes());
+    packet[6..8].copy_from_slice(&sequence.to_be_bytes());
+    packet[8..12].copy_from_slice(&ms_since_midnight(SystemTime::now()).to_be_bytes());
+
+    let checksum = pnet::packet::icmp::checksum(&IcmpPacket::new(packet).unwrap());
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    Ok(())
+}
+
+/// 解析ICMP时间戳应答（类型14）
+pub fn parse_timestamp_reply(
+    buffer: &[u8],
+    offset: usize,
+    ttl: u8,
+    received_at: Instant,
+) -> Option<IcmpTimestampReply> {
+    let icmp = buffer.get(offset..offset + TIMESTAMP_PACKET_LEN)?;
+    if icmp[0] != IcmpTypes::TimestampReply.0 {
+        return None;
+    }
+    let field =
+        |at: usize| u32::from_be_bytes([icmp[at], icmp[at + 1], icmp[at + 2], icmp[at + 3]]);
+
+    Some(IcmpTimestampReply {
+        identifier: u16::from_be_bytes([icmp[4], icmp[5]]),
+        sequence: u16::from_be_bytes([icmp[6], icmp[7]]),
+        ttl,
+        originate: field(8),
+        receive: field(12),
+        transmit: field(16),
+        received_at,
+    })
+}
+
+/// 定位接收数据中的ICMPv4报文，返回偏移量和IP头中的TTL
+///
+/// 原始套接字（以及macOS上的数据报套接字）收到的数据带IP头，
+/// Linux上的数据报套接字只收到ICMP报文，此时TTL需要从辅助数据中获取
+pub fn locate_icmpv4(buffer: &[u8]) -> Option<(usize, Option<u8>)> {
+    // IPv4头的首字节是0x4X，而合法的ICMP类型都小于0x40
+    if buffer.first()? >> 4 != 4 {
+        return Some((0, None));
+    }
+
+    let ipv4_packet = Ipv4Packet::new(buffer)?;
+    if ipv4_packet.get_next_level_protocol() != IpNextHeaderProtocols::Icmp {
+        return None;
+    }
+
+    Some((
+        (ipv4_packet.get_header_length() * 4) as usize,
+        Some(ipv4_packet.get_ttl()),
+    ))
 }
 
 pub fn parse_echo_reply(
     buffer: &[u8],
     offset: usize,
-    expected_id: u16,
-    expected_seq: u16,
-    start_time: Instant,
     ttl: u8,
+    received_at: Instant,
 ) -> Option<IcmpEchoReply> {
     if buffer.len() < offset + IcmpPacket::minimum_packet_size() {
         return None;
@@ -84,18 +478,233 @@ pub fn parse_echo_reply(
     // This is a bit of a hack, but the underlying structure is the same
     let echo_packet = EchoRequestPacket::new(&buffer[offset..])?;
 
-    let seq = echo_packet.get_sequence_number();
-    let id = echo_packet.get_identifier();
+    Some(IcmpEchoReply {
+        identifier: echo_packet.get_identifier(),
+        sequence: echo_packet.get_sequence_number(),
+        ttl,
+        size: buffer.len() - offset,
+        payload: buffer[offset + 8..].to_vec(),
+        received_at,
+        timestamp_source: TimestampSource::Userspace,
+    })
+}
+
+/// 解析ICMPv4差错报文，从其携带的原始IP头和ICMP头中还原出被拒绝的探测
+pub fn parse_error_reply(
+    buffer: &[u8],
+    offset: usize,
+    router: IpAddr,
+    received_at: Instant,
+) -> Option<IcmpErrorReply> {
+    let icmp = buffer.get(offset..)?;
+    // 差错报文头8字节，之后是原始IP头和原始数据的前8字节
+    if icmp.len() < 8 + Ipv4Packet::minimum_packet_size() + 8 {
+        return None;
+    }
 
-    if id != expected_id || seq != expected_seq {
+    let kind = IcmpErrorKind::from_icmpv4(icmp[0], icmp[1])?;
+    let mtu = match kind {
+        // RFC 1191：下一跳MTU位于第6、7字节
+        IcmpErrorKind::FragmentationNeeded => Some(u16::from_be_bytes([icmp[6], icmp[7]]) as u32),
+        _ => None,
+    };
+
+    let original = Ipv4Packet::new(&icmp[8..])?;
+    if original.get_next_level_protocol() != IpNextHeaderProtocols::Icmp {
+        return None;
+    }
+    let original_offset = 8 + (original.get_header_length() * 4) as usize;
+    let echo = icmp.get(original_offset..original_offset + 8)?;
+    if echo[0] != IcmpTypes::EchoRequest.0 && echo[0] != IcmpTypes::Timestamp.0 {
         return None;
     }
 
+    Some(IcmpErrorReply {
+        kind,
+        router,
+        mtu,
+        identifier: u16::from_be_bytes([echo[4], echo[5]]),
+        sequence: u16::from_be_bytes([echo[6], echo[7]]),
+        destination: IpAddr::V4(original.get_destination()),
+        received_at,
+    })
+}
+
+/// 解析ICMPv6差错报文（目的不可达、包过大、超时）
+pub fn parse_error_reply_v6(
+    buffer: &[u8],
+    router: IpAddr,
+    received_at: Instant,
+) -> Option<IcmpErrorReply> {
+    // 差错报文头8字节，之后是40字节的原始IPv6头和原始ICMPv6头
+    if buffer.len() < 8 + 40 + 8 {
+        return None;
+    }
+
+    let kind = IcmpErrorKind::from_icmpv6(buffer[0], buffer[1])?;
+    let mtu = match kind {
+        IcmpErrorKind::FragmentationNeeded => Some(u32::from_be_bytes([
+            buffer[4], buffer[5], buffer[6], buffer[7],
+        ])),
+        _ => None,
+    };
+
+    let original = &buffer[8..48];
+    // 只处理没有扩展头、直接携带ICMPv6的原始包
+    if original[6] != IpNextHeaderProtocols::Icmpv6.0 {
+        return None;
+    }
+    let mut destination = [0u8; 16];
+    destination.copy_from_slice(&original[24..40]);
+
+    let echo = &buffer[48..56];
+    if echo[0] != Icmpv6Types::EchoRequest.0 {
+        return None;
+    }
+
+    Some(IcmpErrorReply {
+        kind,
+        router,
+        mtu,
+        identifier: u16::from_be_bytes([echo[4], echo[5]]),
+        sequence: u16::from_be_bytes([echo[6], echo[7]]),
+        destination: IpAddr::V6(Ipv6Addr::from(destination)),
+        received_at,
+    })
+}
+
+/// 解析ICMPv6 Echo Reply（类型129）
+///
+/// ICMPv6的原始套接字和数据报套接字收到的数据都不带IPv6头，
+/// 跳数限制需要从辅助数据（IPV6_HOPLIMIT）中获取
+pub fn parse_echo_reply_v6(
+    buffer: &[u8],
+    hop_limit: u8,
+    received_at: Instant,
+) -> Option<IcmpEchoReply> {
+    let icmp_packet = Icmpv6Packet::new(buffer)?;
+
+    if icmp_packet.get_icmpv6_type() != Icmpv6Types::EchoReply {
+        return None;
+    }
+
+    let echo_packet = EchoRequestV6Packet::new(buffer)?;
+
     Some(IcmpEchoReply {
-        identifier: id,
-        sequence: seq,
-        ttl,
-        size: buffer.len() - offset,
-        rtt: start_time.elapsed(),
+        identifier: echo_packet.get_identifier(),
+        sequence: echo_packet.get_sequence_number(),
+        ttl: hop_limit,
+        size: buffer.len(),
+        payload: buffer[8..].to_vec(),
+        received_at,
+        timestamp_source: TimestampSource::Userspace,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo_v6_round_trip() {
+        let mut buffer = vec![0u8; 64];
+        let request = IcmpEchoRequest::new(0x1234, 7, buffer.len());
+        request
+            .create_packet_v6(&mut buffer, Ipv6Addr::LOCALHOST, Ipv6Addr::LOCALHOST)
+            .unwrap();
+        assert_eq!(buffer[0], 128);
+
+        // 把请求改成回复后应能被解析
+        buffer[0] = 129;
+        let reply = parse_echo_reply_v6(&buffer, 64, Instant::now()).unwrap();
+        assert_eq!(reply.identifier, 0x1234);
+        assert_eq!(reply.sequence, 7);
+        assert_eq!(reply.ttl, 64);
+    }
+
+    #[test]
+    fn test_parse_error_reply_matches_embedded_probe() {
+        let mut echo = vec![0u8; 16];
+        IcmpEchoRequest::new(0xbeef, 42, echo.len())
+            .create_packet(&mut echo)
+            .unwrap();
+
+        // 原始IPv4头：目的地址8.8.8.8，协议ICMP
+        let mut original = vec![0u8; 20];
+        original[0] = 0x45;
+        original[9] = 1;
+        original[16..20].copy_from_slice(&[8, 8, 8, 8]);
+
+        // Fragmentation Needed，下一跳MTU为1400
+        let mut error = vec![3, 4, 0, 0, 0, 0, 0x05, 0x78];
+        error.extend_from_slice(&original);
+        error.extend_from_slice(&echo[..8]);
+
+        let router: IpAddr = "192.0.2.1".parse().unwrap();
+        let reply = parse_error_reply(&error, 0, router, Instant::now()).unwrap();
+        assert_eq!(reply.kind, IcmpErrorKind::FragmentationNeeded);
+        assert_eq!(reply.mtu, Some(1400));
+        assert_eq!(reply.identifier, 0xbeef);
+        assert_eq!(reply.sequence, 42);
+        assert_eq!(reply.destination, "8.8.8.8".parse::<IpAddr>().unwrap());
+        assert!(matches!(
+            reply.to_error(),
+            PingError::FragmentationNeeded { mtu: 1400, .. }
+        ));
+    }
+
+    #[test]
+    fn test_verify_payload_with_pattern() {
+        assert_eq!(parse_pattern("ff0").unwrap(), vec![0x0f, 0xf0]);
+        assert!(parse_pattern("xyz").is_err());
+
+        let mut sent = vec![0u8; 64];
+        IcmpEchoRequest::new(1, 1, sent.len())
+            .with_pattern(vec![0xab, 0xcd])
+            .create_packet(&mut sent)
+            .unwrap();
+        assert_eq!(
+            &sent[8 + PAYLOAD_HEADER_LEN..8 + PAYLOAD_HEADER_LEN + 2],
+            &[0xab, 0xcd]
+        );
+
+        let payload = &sent[8..];
+        assert_eq!(verify_payload(payload, payload), PayloadCheck::Intact);
+        assert_eq!(
+            verify_payload(payload, &payload[..20]),
+            PayloadCheck::Truncated
+        );
+        let mut mangled = payload.to_vec();
+        mangled[30] ^= 0x01;
+        assert_eq!(verify_payload(payload, &mangled), PayloadCheck::Corrupted);
+    }
+
+    #[test]
+    fn test_timestamp_reply_estimates_offset_across_midnight() {
+        let mut buffer = vec![0u8; TIMESTAMP_PACKET_LEN];
+        create_timestamp_request(&mut buffer, 0x1234, 3).unwrap();
+        assert_eq!(buffer[0], 13);
+
+        // 对端时钟快了100ms，去程10ms、回程20ms，发起时间在午夜前5ms；
+        // 路径不对称的一半（5ms）会计入估算的偏差
+        let originate = (MS_PER_DAY - 5) as u32;
+        buffer[0] = 14;
+        buffer[8..12].copy_from_slice(&originate.to_be_bytes());
+        buffer[12..16].copy_from_slice(&105u32.to_be_bytes());
+        buffer[16..20].copy_from_slice(&106u32.to_be_bytes());
+
+        let reply = parse_timestamp_reply(&buffer, 0, 64, Instant::now()).unwrap();
+        assert_eq!(reply.identifier, 0x1234);
+        assert_eq!(reply.sequence, 3);
+
+        let estimate = reply.estimate(26).unwrap();
+        assert_eq!(estimate.forward_ms, 110);
+        assert_eq!(estimate.return_ms, -80);
+        assert_eq!(estimate.offset_ms(), 95.0);
+
+        // 最高位表示非标准时间戳
+        let mut nonstandard = reply;
+        nonstandard.receive |= 0x8000_0000;
+        assert_eq!(nonstandard.estimate(26), None);
+    }
+}
@@ -0,0 +1,55 @@
+use ipnetwork::IpNetwork;
+use pnet::datalink::{self, MacAddr, NetworkInterface};
+
+use crate::socket::Binding;
+
+/// 与目标网段直连的以太网卡，ARP和NDP探测都从这里发出
+#[derive(Debug, Clone)]
+pub struct DirectLink {
+    pub interface: NetworkInterface,
+    pub source_mac: MacAddr,
+    /// 网卡在该网段上的地址及前缀，与`network`属于同一协议族
+    pub subnet: IpNetwork,
+}
+
+impl DirectLink {
+    /// 查找与`network`有交集的、已启用的非回环以太网卡，只匹配与`network`同族的地址
+    ///
+    /// 指定了网卡或源地址时，只使用该网卡或拥有该地址的网卡
+    pub fn for_network(network: &IpNetwork, binding: &Binding) -> Option<Self> {
+        datalink::interfaces()
+            .into_iter()
+            .filter(|iface| iface.is_up() && !iface.is_loopback())
+            .filter(|iface| {
+                binding
+                    .interface
+                    .as_ref()
+                    .is_none_or(|name| *name == iface.name)
+            })
+            .find_map(|iface| {
+                let source_mac = iface.mac.filter(|mac| !mac.is_zero())?;
+                let subnet = iface.ips.iter().copied().find(|subnet| {
+                    overlaps(subnet, network)
+                        && binding.source.is_none_or(|source| source == subnet.ip())
+                })?;
+                Some(Self {
+                    interface: iface,
+                    source_mac,
+                    subnet,
+                })
+            })
+    }
+}
+
+/// 两个网段是否同族且有交集
+fn overlaps(subnet: &IpNetwork, network: &IpNetwork) -> bool {
+    match (subnet, network) {
+        (IpNetwork::V4(subnet), IpNetwork::V4(network)) => {
+            subnet.contains(network.network()) || network.contains(subnet.network())
+        }
+        (IpNetwork::V6(subnet), IpNetwork::V6(network)) => {
+            subnet.contains(network.network()) || network.contains(subnet.network())
+        }
+        _ => false,
+    }
+}
@@ -1,34 +1,72 @@
+mod arp;
 mod cli;
 mod database;
+mod dispatcher;
 mod error;
+mod histogram;
 mod host;
 mod icmp;
+mod link;
 mod monitor;
+mod mtr;
+mod ndp;
 mod output;
+mod pacer;
 mod pinger;
+mod pmtu;
+mod rto;
+mod socket;
 mod stats;
+mod tcp;
+mod trace;
+mod tracker;
+mod udp;
 mod ui;
 
 use anyhow::Result;
 use clap::Parser;
 use cli::Cli;
 use colored::Colorize;
-use error::PingError;
-use host::{load_hosts_from_file, resolve_host, PingTarget};
+use error::{ExitStatus, PingError};
+use histogram::parse_percentiles;
+use host::{generate_targets, load_hosts_from_file, resolve_host, split_target_dscp, PingTarget};
+use icmp::{ICMP_HEADER_LEN, MIN_ECHO_SIZE};
 use monitor::NetworkMonitor;
 use output::{
-    print_csv_summary, print_json_summary, print_ping_result, print_ping_start, print_ping_summary,
+    csv_header, format_fping_report, print_csv_summary, print_fping_sample, print_json_summary,
+    print_mtr_csv, print_mtr_json, print_mtr_report, print_ping_result, print_ping_start,
+    print_ping_summary, print_pmtu_csv, print_pmtu_json, print_pmtu_result, print_pmtu_start,
+    print_pmtu_step, print_responder_start, print_tcp_start, print_timestamp_start,
+    print_trace_csv, print_trace_hop, print_trace_json, print_trace_start, print_udp_start,
 };
-use pinger::Pinger;
-use stats::PingStats;
-use std::collections::HashMap;
+use pinger::{Pinger, RetryPolicy};
+use socket::{parse_dscp_list, Binding, Family, SocketOptions};
+use stats::{exit_status, PingStats};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
 use std::process;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tcp::TcpPinger;
 use tokio::sync::mpsc;
+use tokio::time;
+use udp::UdpPinger;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    // clap默认以2退出，与fping的“主机名无法解析”冲突，参数错误统一以3退出
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            let status = if e.use_stderr() {
+                ExitStatus::InvalidArgument
+            } else {
+                ExitStatus::AllReachable
+            };
+            process::exit(status.code());
+        }
+    };
 
     // 检查是否使用UI模式（只有在Monitor子命令中才有UI选项）
     let using_ui = if let Some(cli::Commands::Monitor { ui, .. }) = &cli.command {
@@ -44,6 +82,196 @@ async fn main() -> Result<()> {
                 // 摘要命令需要在收集完统计信息后处理
                 // 所以这里不立即返回
             }
+            cli::Commands::Trace {
+                host,
+                max_hops,
+                queries,
+                timeout,
+                format,
+                numeric,
+            } => {
+                let addr = match resolve_host(host) {
+                    Ok(addr) => addr,
+                    Err(e) => {
+                        eprintln!("Could not resolve host {}: {}", host, e);
+                        process::exit(ExitStatus::UnresolvedHost.code());
+                    }
+                };
+                let target = PingTarget::new(host.clone(), addr);
+                let text = format.as_str() != "json" && format.as_str() != "csv";
+
+                if text {
+                    print_trace_start(host, &addr.to_string(), *max_hops, cli.size);
+                }
+
+                let result = trace::trace(
+                    target,
+                    *max_hops,
+                    *queries,
+                    *timeout,
+                    cli.size,
+                    &Binding::new(cli.source, cli.interface.clone()),
+                    !*numeric,
+                    |hop| {
+                        if text {
+                            print_trace_hop(hop);
+                        }
+                    },
+                )
+                .await;
+
+                match result {
+                    Ok(trace) => match format.as_str() {
+                        "json" => println!("{}", print_trace_json(&trace)),
+                        "csv" => println!("{}", print_trace_csv(&trace)),
+                        _ => {}
+                    },
+                    Err(PingError::PermissionDenied) => exit_permission_denied(),
+                    Err(e) => {
+                        eprintln!("Error tracing {}: {}", host, e);
+                        process::exit(ExitStatus::SystemError.code());
+                    }
+                }
+                return Ok(());
+            }
+            cli::Commands::Mtr {
+                host,
+                interval,
+                cycles,
+                max_hops,
+                timeout,
+                format,
+                numeric,
+                report,
+            } => {
+                let addr = match resolve_host(host) {
+                    Ok(addr) => addr,
+                    Err(e) => {
+                        eprintln!("Could not resolve host {}: {}", host, e);
+                        process::exit(ExitStatus::UnresolvedHost.code());
+                    }
+                };
+                let target = PingTarget::new(host.clone(), addr);
+
+                let running = Arc::new(Mutex::new(true));
+                let r = running.clone();
+                if let Err(e) = ctrlc::set_handler(move || {
+                    let mut running = r.lock().unwrap();
+                    *running = false;
+                }) {
+                    eprintln!("Error setting Ctrl-C handler: {}", e);
+                    process::exit(ExitStatus::SystemError.code());
+                }
+
+                // 报告模式或输出JSON/CSV时不刷新终端，只在结束后输出报告
+                let live = !*report && format.as_str() != "json" && format.as_str() != "csv";
+                let path_ui = if live {
+                    match ui::PathUI::enter() {
+                        Ok(path_ui) => Some(path_ui),
+                        Err(e) => {
+                            eprintln!("UI error: {}", e);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                let result = mtr::run(
+                    target,
+                    *max_hops,
+                    *cycles,
+                    *interval,
+                    *timeout,
+                    cli.size,
+                    &Binding::new(cli.source, cli.interface.clone()),
+                    !*numeric,
+                    running,
+                    |report| {
+                        if let Some(path_ui) = &path_ui {
+                            let _ = path_ui.render(report);
+                        }
+                    },
+                )
+                .await;
+
+                if let Some(path_ui) = path_ui {
+                    let _ = path_ui.leave();
+                }
+
+                match result {
+                    Ok(report) => match format.as_str() {
+                        "json" => println!("{}", print_mtr_json(&report)),
+                        "csv" => println!("{}", print_mtr_csv(&report)),
+                        _ => print_mtr_report(&report),
+                    },
+                    Err(PingError::PermissionDenied) => exit_permission_denied(),
+                    Err(e) => {
+                        eprintln!("Error probing path to {}: {}", host, e);
+                        process::exit(ExitStatus::SystemError.code());
+                    }
+                }
+                return Ok(());
+            }
+            cli::Commands::Pmtu {
+                host,
+                max_mtu,
+                queries,
+                timeout,
+                format,
+            } => {
+                let addr = match resolve_host(host) {
+                    Ok(addr) => addr,
+                    Err(e) => {
+                        eprintln!("Could not resolve host {}: {}", host, e);
+                        process::exit(ExitStatus::UnresolvedHost.code());
+                    }
+                };
+                let target = PingTarget::new(host.clone(), addr);
+                let text = format.as_str() != "json" && format.as_str() != "csv";
+
+                if text {
+                    print_pmtu_start(host, &addr.to_string(), cli.size, *max_mtu);
+                }
+
+                let result = pmtu::discover(
+                    target,
+                    cli.size,
+                    *max_mtu,
+                    *queries,
+                    *timeout,
+                    cli.ttl,
+                    &Binding::new(cli.source, cli.interface.clone()),
+                    |step| {
+                        if text {
+                            print_pmtu_step(step);
+                        }
+                    },
+                )
+                .await;
+
+                match result {
+                    Ok(result) => match format.as_str() {
+                        "json" => println!("{}", print_pmtu_json(&result)),
+                        "csv" => println!("{}", print_pmtu_csv(&result)),
+                        _ => print_pmtu_result(&result),
+                    },
+                    Err(PingError::PermissionDenied) => exit_permission_denied(),
+                    Err(e) => {
+                        eprintln!("Error discovering path MTU to {}: {}", host, e);
+                        process::exit(ExitStatus::SystemError.code());
+                    }
+                }
+                return Ok(());
+            }
+            cli::Commands::Responder { port } => {
+                if let Err(e) = udp::run_responder(*port, |addr| print_responder_start(&addr)).await
+                {
+                    eprintln!("Error running UDP responder on port {}: {}", port, e);
+                    process::exit(ExitStatus::SystemError.code());
+                }
+                return Ok(());
+            }
             cli::Commands::Monitor {
                 network,
                 interval,
@@ -51,22 +279,33 @@ async fn main() -> Result<()> {
                 changes_only,
                 resolve_mac,
                 ui,
+                rate,
+                packet_interval,
+                source,
+                interface,
             } => {
+                pacer::configure(*rate, *packet_interval);
                 // 启动网络监控模式
                 match NetworkMonitor::new(network, *interval, *resolve_mac, *changes_only, *ui) {
-                    Ok(mut monitor) => {
+                    Ok(monitor) => {
+                        let mut monitor =
+                            monitor.with_binding(Binding::new(*source, interface.clone()));
                         if let Err(e) = monitor.start_monitoring().await {
                             // 只有在非UI模式下才打印错误信息到控制台
                             if !*ui {
                                 eprintln!("Error during network monitoring: {}", e);
                             }
-                            process::exit(1);
+                            process::exit(ExitStatus::SystemError.code());
                         }
                         return Ok(());
                     }
                     Err(e) => {
                         eprintln!("Error setting up network monitor: {}", e);
-                        process::exit(1);
+                        let status = match e {
+                            PingError::InvalidAddress(_) => ExitStatus::InvalidArgument,
+                            _ => ExitStatus::SystemError,
+                        };
+                        process::exit(status.code());
                     }
                 }
             }
@@ -80,15 +319,26 @@ async fn main() -> Result<()> {
             Ok(file_hosts) => hosts.extend(file_hosts),
             Err(e) => {
                 eprintln!("Error loading hosts from file {}: {}", file_path, e);
-                process::exit(1);
+                process::exit(ExitStatus::SystemError.code());
             }
         }
     }
 
+    // fping -g：把网段或起止地址展开为目标列表
+    if cli.generate {
+        hosts = match generate_targets(&hosts) {
+            Ok(generated) => generated,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(ExitStatus::InvalidArgument.code());
+            }
+        };
+    }
+
     // 如果没有提供主机且没有使用子命令，显示错误信息
     if hosts.is_empty() && cli.command.is_none() {
         eprintln!("Error: No target hosts specified. Use --help for usage information.");
-        process::exit(1);
+        process::exit(ExitStatus::InvalidArgument.code());
     }
 
     // 如果没有提供主机但使用了摘要子命令，显示错误信息
@@ -97,67 +347,236 @@ async fn main() -> Result<()> {
             eprintln!(
                 "Error: No target hosts specified for summary. Use --help for usage information."
             );
-            process::exit(1);
+            process::exit(ExitStatus::InvalidArgument.code());
         }
     }
 
+    let pattern = match cli.pattern.as_deref().map(icmp::parse_pattern).transpose() {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(ExitStatus::InvalidArgument.code());
+        }
+    };
+
+    // fping兼容的输出（-a、-u、-C）代替ping风格的输出
+    let fping_output = cli.alive || cli.unreach || cli.vcount.is_some();
+    let icmp_echo = cli.tcp.is_none() && cli.udp.is_none() && !cli.icmp_timestamp;
+    // 与fping -b相同，fping兼容模式下-s是不含ICMP头的数据大小，默认发送64字节的包
+    let size = if fping_output && icmp_echo {
+        cli.size + ICMP_HEADER_LEN
+    } else {
+        cli.size
+    };
+
+    // 探测头放不下时无法识别损坏或伪造的应答，TCP和UDP探测不受影响
+    if icmp_echo && size < MIN_ECHO_SIZE {
+        eprintln!(
+            "Error: ICMP packet size must be at least {} bytes to hold the probe header",
+            MIN_ECHO_SIZE
+        );
+        process::exit(ExitStatus::InvalidArgument.code());
+    }
+
+    // 与fping -B的取值范围相同
+    if !(1.0..=5.0).contains(&cli.backoff) {
+        eprintln!("Error: backoff factor must be between 1.0 and 5.0");
+        process::exit(ExitStatus::InvalidArgument.code());
+    }
+
+    let percentiles = match parse_percentiles(&cli.percentiles) {
+        Ok(percentiles) => percentiles,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(ExitStatus::InvalidArgument.code());
+        }
+    };
+
+    let dscp = match cli.dscp.as_deref().map(parse_dscp_list).transpose() {
+        Ok(dscp) => dscp,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(ExitStatus::InvalidArgument.code());
+        }
+    };
+
     // Setup signal handling for graceful termination
     let running = Arc::new(Mutex::new(true));
     let r = running.clone();
-    ctrlc::set_handler(move || {
+    if let Err(e) = ctrlc::set_handler(move || {
         let mut running = r.lock().unwrap();
         *running = false;
         println!("\nInterrupted, exiting...");
-    })?;
+    }) {
+        eprintln!("Error setting Ctrl-C handler: {}", e);
+        process::exit(ExitStatus::SystemError.code());
+    }
+
+    // -l与--count 0相同，一直探测直到中断或到达截止时间；
+    // -C与fping相同，每个目标正好发送N个探测，不重试
+    let count = if cli.loop_mode {
+        0
+    } else {
+        cli.vcount.unwrap_or(cli.count)
+    };
+    let retry = RetryPolicy {
+        backoff: cli.backoff,
+        adaptive_timeout: cli.adaptive_timeout,
+        ..RetryPolicy::new(if cli.vcount.is_some() { 0 } else { cli.retry })
+    };
+    let verbose = !cli.quiet && !using_ui && !fping_output;
+    let deadline = cli
+        .deadline
+        .map(|secs| time::Instant::now() + Duration::from_secs(secs));
+
+    // 所有目标的探测共用一个发包节奏
+    pacer::configure(cli.rate, cli.interval);
 
     // Channel for collecting ping results
     let (tx, mut rx) = mpsc::channel(100);
+    let binding = Binding::new(cli.source, cli.interface.clone());
 
     // Start ping tasks for each host
     let mut tasks = vec![];
-    for host_str in hosts {
+    // 设置阶段遇到的问题，最后与目标的可达情况合并为退出码
+    let mut status = ExitStatus::AllReachable;
+    // 目标按输入顺序排列，fping兼容的输出按这个顺序打印
+    let mut targets: Vec<String> = vec![];
+    // 已经在探测的(地址, DSCP)，相同的组合共用一个socket，重复的目标只探测一次
+    let mut probed: HashSet<(IpAddr, u8)> = HashSet::new();
+    for host_spec in hosts {
+        let (host_str, target_classes) = match split_target_dscp(&host_spec) {
+            Ok((host_str, target_classes)) => (host_str, target_classes.or_else(|| dscp.clone())),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(ExitStatus::InvalidArgument.code());
+            }
+        };
         match resolve_host(&host_str) {
             Ok(addr) => {
                 let target = PingTarget::new(host_str.clone(), addr);
 
-                if !cli.quiet && !using_ui {
-                    print_ping_start(&target.name, &target.addr.to_string(), cli.size);
-                }
-
-                match Pinger::new(target.clone(), cli.size, cli.ttl) {
-                    Ok(pinger) => {
-                        let tx_clone = tx.clone();
-                        let task = tokio::spawn(async move {
-                            if let Err(e) = pinger
-                                .ping_multiple(
-                                    cli.count,
-                                    cli.period,
-                                    cli.timeout,
-                                    cli.retry,
-                                    tx_clone,
-                                )
-                                .await
-                            {
-                                eprintln!("Error pinging {}: {}", target.name, e);
-                            }
-                        });
-                        tasks.push(task);
+                if target_classes.is_some() && (cli.tcp.is_some() || cli.udp.is_some()) {
+                    eprintln!("Error: DSCP marking is only supported for ICMP probes");
+                    process::exit(ExitStatus::InvalidArgument.code());
+                }
+
+                // TCP和UDP不支持DSCP，重复的目标按地址去重
+                if (cli.tcp.is_some() || cli.udp.is_some()) && is_duplicate(&mut probed, &target) {
+                    continue;
+                }
+
+                if let Some(port) = cli.tcp {
+                    if verbose {
+                        print_tcp_start(&target.name, &target.addr.to_string(), port);
                     }
-                    Err(PingError::PermissionDenied) => {
-                        eprintln!(
-                            "{}",
-                            "Error: Raw sockets require root privileges. Please run with sudo."
-                                .red()
-                        );
-                        process::exit(1);
+
+                    let pinger = TcpPinger::new(target.clone(), port, cli.ttl, &binding);
+                    targets.push(target.label());
+                    let tx_clone = tx.clone();
+                    let task = tokio::spawn(async move {
+                        if let Err(e) = pinger
+                            .ping_multiple(count, cli.period, cli.timeout, retry, tx_clone)
+                            .await
+                        {
+                            eprintln!("Error pinging {}: {}", target.name, e);
+                        }
+                    });
+                    tasks.push(task);
+                    continue;
+                }
+
+                if let Some(port) = cli.udp {
+                    if verbose {
+                        print_udp_start(&target.name, &target.addr.to_string(), port, size);
                     }
-                    Err(e) => {
-                        eprintln!("Error creating pinger for {}: {}", host_str, e);
+
+                    match UdpPinger::new(target.clone(), port, size, cli.ttl, &binding) {
+                        Ok(pinger) => {
+                            targets.push(target.label());
+                            let tx_clone = tx.clone();
+                            let task = tokio::spawn(async move {
+                                if let Err(e) = pinger
+                                    .ping_multiple(count, cli.period, cli.timeout, retry, tx_clone)
+                                    .await
+                                {
+                                    eprintln!("Error pinging {}: {}", target.name, e);
+                                }
+                            });
+                            tasks.push(task);
+                        }
+                        Err(e) => {
+                            eprintln!("Error creating UDP pinger for {}: {}", host_str, e);
+                            status = status.max(ExitStatus::SystemError);
+                        }
+                    }
+                    continue;
+                }
+
+                // 每个DSCP一个Pinger，不同DSCP的探测使用不同的socket
+                let class_targets: Vec<PingTarget> = match &target_classes {
+                    Some(classes) => classes
+                        .iter()
+                        .map(|dscp| target.clone().with_dscp(Some(*dscp)))
+                        .collect(),
+                    None => vec![target.clone()],
+                };
+                for target in class_targets {
+                    if is_duplicate(&mut probed, &target) {
+                        continue;
+                    }
+
+                    if verbose {
+                        if cli.icmp_timestamp {
+                            print_timestamp_start(&target.label(), &target.addr.to_string());
+                        } else {
+                            print_ping_start(&target.label(), &target.addr.to_string(), size);
+                        }
+                    }
+
+                    let options = SocketOptions {
+                        family: Family::of(&target.addr),
+                        ttl: cli.ttl,
+                        dont_fragment: false,
+                        binding: binding.clone(),
+                        dscp: target.dscp.unwrap_or(0),
+                    };
+                    match Pinger::with_socket_options(target.clone(), size, options) {
+                        Ok(pinger) => {
+                            let pinger = pinger
+                                .with_pattern(pattern.clone())
+                                .with_timestamp_requests(cli.icmp_timestamp);
+                            if cli.icmp_timestamp && !pinger.supports_timestamp() {
+                                eprintln!(
+                                    "Error: ICMP timestamp requests for {} need an IPv4 target and a raw socket (run with sudo)",
+                                    host_str
+                                );
+                                status = status.max(ExitStatus::InvalidArgument);
+                                continue;
+                            }
+                            targets.push(target.label());
+                            let tx_clone = tx.clone();
+                            let task = tokio::spawn(async move {
+                                if let Err(e) = pinger
+                                    .ping_multiple(count, cli.period, cli.timeout, retry, tx_clone)
+                                    .await
+                                {
+                                    eprintln!("Error pinging {}: {}", target.name, e);
+                                }
+                            });
+                            tasks.push(task);
+                        }
+                        Err(PingError::PermissionDenied) => exit_permission_denied(),
+                        Err(e) => {
+                            eprintln!("Error creating pinger for {}: {}", host_str, e);
+                            status = status.max(ExitStatus::SystemError);
+                        }
                     }
                 }
             }
             Err(e) => {
                 eprintln!("Could not resolve host {}: {}", host_str, e);
+                status = status.max(ExitStatus::UnresolvedHost);
             }
         }
     }
@@ -167,21 +586,83 @@ async fn main() -> Result<()> {
 
     // Track statistics for each host
     let mut host_stats: HashMap<String, PingStats> = HashMap::new();
+    // -C模式下每个目标的RTT样本，丢失的探测为None
+    let mut samples: HashMap<String, Vec<Option<Duration>>> = HashMap::new();
+    // -a/-u模式下已经确定了结果的目标
+    let mut decided: HashSet<String> = HashSet::new();
+    let name_width = targets.iter().map(|name| name.len()).max().unwrap_or(0);
 
     // Process results as they come in
-    while let Some(response) = rx.recv().await {
-        if !cli.quiet && !using_ui {
+    loop {
+        let response = match deadline {
+            Some(deadline) => tokio::select! {
+                response = rx.recv() => response,
+                // 到达截止时间，不再等待还在进行的探测
+                _ = time::sleep_until(deadline) => break,
+            },
+            None => rx.recv().await,
+        };
+        // 所有探测任务都已结束
+        let response = match response {
+            Some(response) => response,
+            None => break,
+        };
+
+        if verbose {
             print_ping_result(&response, cli.timestamp);
         }
 
         let stats = host_stats
-            .entry(response.target.name.clone())
-            .or_insert_with(PingStats::new);
+            .entry(response.target.label())
+            .or_insert_with(|| PingStats {
+                dscp: response.target.dscp,
+                ..PingStats::new()
+            });
+        stats.record_source(response.source);
+
+        match &response.error {
+            None => {
+                if response.class.is_counted() {
+                    stats.update_with_success(response.seq, response.rtt.unwrap());
+                    stats.record_payload_check(response.payload_check);
+                    stats.record_timestamp_source(response.timestamp_source);
+                    stats.record_probe(response.probe);
+                    stats.record_attempt(&response);
+                }
+                stats.record_reply_class(response.class);
+            }
+            Some(e) => {
+                stats.update_with_failure(response.seq, e);
+                stats.record_attempt(&response);
+            }
+        }
+
+        // fping -a/-u：与fping一样，目标的结果一确定就输出，不等其他目标结束。
+        // 未指定--max-loss时第一个回复即可判定在线，否则要等全部探测结束
+        if (cli.alive || cli.unreach) && !decided.contains(&response.target.label()) {
+            let finished = count > 0 && stats.probes >= count;
+            if finished || (cli.max_loss.is_none() && stats.received > 0) {
+                decided.insert(response.target.label());
+                if stats.is_reachable(cli.max_loss) == cli.alive {
+                    println!("{}", response.target.label());
+                }
+            }
+        }
 
-        if response.is_success() {
-            stats.update_with_success(response.seq, response.rtt.unwrap());
-        } else {
-            stats.update_with_failure(response.seq);
+        // 迟到和重复的回复不是新的样本
+        if cli.vcount.is_some() && response.class.is_counted() {
+            let target_samples = samples.entry(response.target.label()).or_default();
+            let index = target_samples.len();
+            target_samples.push(response.rtt.filter(|_| response.is_success()));
+            if !cli.quiet && !cli.alive && !cli.unreach {
+                print_fping_sample(
+                    &response.target.label(),
+                    name_width,
+                    index,
+                    &response,
+                    stats,
+                );
+            }
         }
 
         // Check if we should exit early due to Ctrl-C
@@ -190,21 +671,42 @@ async fn main() -> Result<()> {
         }
     }
 
-    // Print summary for each host
-    if let Some(command) = &cli.command {
+    // 按--max-loss判断每个目标是否可达，-a/-u和退出码使用同一个标准
+    let reachable = |name: &String| {
+        host_stats
+            .get(name)
+            .is_some_and(|stats| stats.is_reachable(cli.max_loss))
+    };
+    let status = exit_status(status, &targets, &host_stats, cli.max_loss);
+
+    if cli.alive || cli.unreach {
+        // 被中断或到达截止时间时还没有结果的目标，按已有的统计判定
+        for name in targets.iter().filter(|name| !decided.contains(*name)) {
+            if reachable(name) == cli.alive {
+                println!("{}", name);
+            }
+        }
+    } else if cli.vcount.is_some() {
+        // fping -C：每个目标的全部RTT样本，与fping一样输出到stderr
+        for name in &targets {
+            let target_samples = samples.get(name).map_or(&[][..], |s| s.as_slice());
+            eprintln!("{}", format_fping_report(name, name_width, target_samples));
+        }
+    } else if let Some(command) = &cli.command {
+        // Print summary for each host
         // 其他命令已经在前面处理过了
         if let cli::Commands::Summary { format } = command {
             match format.as_str() {
                 "json" => {
                     for (host, stats) in &host_stats {
-                        println!("{}", print_json_summary(host, stats));
+                        println!("{}", print_json_summary(host, stats, &percentiles));
                     }
                 }
                 "csv" => {
                     // Print header only once
-                    println!("host,packets_transmitted,packets_received,packet_loss_percent,rtt_min_ms,rtt_avg_ms,rtt_max_ms");
+                    println!("{}", csv_header(&percentiles));
                     for (host, stats) in &host_stats {
-                        let csv = print_csv_summary(host, stats);
+                        let csv = print_csv_summary(host, stats, &percentiles);
                         // Skip the header line
                         if let Some(pos) = csv.find('\n') {
                             println!("{}", &csv[pos + 1..]);
@@ -213,16 +715,44 @@ async fn main() -> Result<()> {
                 }
                 _ => {
                     for (host, stats) in &host_stats {
-                        print_ping_summary(host, stats);
+                        print_ping_summary(host, stats, &percentiles);
                     }
                 }
             }
         }
     } else {
         for (host, stats) in &host_stats {
-            print_ping_summary(host, stats);
+            print_ping_summary(host, stats, &percentiles);
         }
     }
 
+    if status != ExitStatus::AllReachable {
+        process::exit(status.code());
+    }
     Ok(())
 }
+
+/// 目标的(地址, DSCP)已经在探测时提示并返回true，否则登记下来
+///
+/// 同一地址的两个探测会共用统计的标签，结果被重复计数
+fn is_duplicate(probed: &mut HashSet<(IpAddr, u8)>, target: &PingTarget) -> bool {
+    if probed.insert((target.addr, target.dscp.unwrap_or(0))) {
+        return false;
+    }
+    eprintln!(
+        "Warning: {} ({}) is already being probed, skipping duplicate",
+        target.label(),
+        target.addr
+    );
+    true
+}
+
+/// 没有创建ICMP socket的权限时提示用户并以系统调用失败退出
+fn exit_permission_denied() -> ! {
+    eprintln!(
+        "{}",
+        "Error: ICMP sockets require root privileges or a matching net.ipv4.ping_group_range. Please run with sudo."
+            .red()
+    );
+    process::exit(ExitStatus::SystemError.code());
+}
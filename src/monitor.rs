@@ -4,17 +4,20 @@ use colored::Colorize;
 use futures::future::join_all;
 use ipnetwork::IpNetwork;
 use std::collections::{HashMap, HashSet};
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv6Addr};
 use std::process::Command;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::task;
 use tokio::time;
 
+use crate::arp::{self, ArpInterface};
 use crate::database::Database;
 use crate::error::PingError;
-use crate::host::PingTarget;
+use crate::host::{resolve_hostname, PingTarget};
+use crate::ndp::{self, NdpInterface};
 use crate::pinger::Pinger;
+use crate::socket::{Binding, Family, SocketOptions};
 use crate::ui::{CharacterUI, DeviceUIStatus};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -44,6 +47,12 @@ pub struct NetworkMonitor {
     last_scan: Option<DateTime<Local>>,
     use_ui: bool,
     db: Option<Database>,
+    /// 与被监控网段直连的网卡，直连的主机用ARP探测
+    arp: Option<ArpInterface>,
+    /// 与被监控的IPv6网段直连的网卡，用组播ping和邻居发现探测
+    ndp: Option<NdpInterface>,
+    /// 扫描使用的源地址和网卡
+    binding: Binding,
 }
 
 impl NetworkMonitor {
@@ -72,6 +81,9 @@ impl NetworkMonitor {
             None
         };
 
+        let arp = ArpInterface::for_network(&network, &Binding::default());
+        let ndp = NdpInterface::for_network(&network, &Binding::default());
+
         Ok(Self {
             network,
             scan_interval: Duration::from_secs(scan_interval_secs),
@@ -81,9 +93,20 @@ impl NetworkMonitor {
             last_scan: None,
             use_ui,
             db,
+            arp,
+            ndp,
+            binding: Binding::default(),
         })
     }
 
+    /// 从指定的源地址和网卡扫描，ARP和邻居发现也只使用该网卡
+    pub fn with_binding(mut self, binding: Binding) -> Self {
+        self.arp = ArpInterface::for_network(&self.network, &binding);
+        self.ndp = NdpInterface::for_network(&self.network, &binding);
+        self.binding = binding;
+        self
+    }
+
     pub async fn start_monitoring(&mut self) -> Result<(), PingError> {
         if !self.use_ui {
             println!("Starting network monitoring for {}", self.network);
@@ -239,11 +262,24 @@ impl NetworkMonitor {
             // );
         }
 
+        // 直连网段中的主机先用ARP（IPv6为邻居发现）探测，不回应ping的设备也能发现，同时得到MAC
+        let link_macs = match self.network {
+            IpNetwork::V4(_) => self.arp_sweep().await,
+            IpNetwork::V6(_) => self.ndp_discover().await,
+        };
+
         // 创建一个任务集合，用于存储所有的异步ping任务
         let mut ping_tasks = Vec::new();
 
+        // IPv6网段通常太大，无法逐个地址ping，只依靠邻居发现
+        let hosts: Vec<IpAddr> = if self.is_enumerable() {
+            self.network.iter().collect()
+        } else {
+            Vec::new()
+        };
+
         // 并行扫描网络中的所有IP地址
-        for ip in self.network.iter() {
+        for ip in hosts {
             // 特殊处理本地回环地址，始终认为它是在线的
             let is_localhost = ip.to_string().starts_with("127.");
 
@@ -293,17 +329,26 @@ impl NetworkMonitor {
                 continue;
             }
 
-            let target = PingTarget {
-                name: ip.to_string(),
-                addr: ip,
-            };
+            // ARP或邻居发现已经确认在线，无需再ping
+            if link_macs.contains_key(&ip) {
+                continue;
+            }
+
+            let target = PingTarget::new(ip.to_string(), ip);
 
             let use_ui = self.use_ui;
+            let options = SocketOptions {
+                family: Family::of(&ip),
+                ttl: 64,
+                dont_fragment: false,
+                binding: self.binding.clone(),
+                dscp: 0,
+            };
 
             // 创建一个异步任务来ping这个IP
             ping_tasks.push(task::spawn(async move {
                 // 使用较短的超时时间来加快扫描速度
-                match Pinger::new(target.clone(), 56, 64) {
+                match Pinger::with_socket_options(target.clone(), 56, options) {
                     Ok(pinger) => {
                         let response = pinger.ping_once(0, 500).await;
                         (ip, response.is_success(), target)
@@ -324,24 +369,34 @@ impl NetworkMonitor {
         // 等待所有ping任务完成
         let ping_results = join_all(ping_tasks).await;
 
-        // 处理ping结果
-        for (ip, is_up, _target) in ping_results.into_iter().flatten() {
+        // 处理ARP/邻居发现和ping结果
+        let link_hosts = link_macs.into_iter().map(|(ip, mac)| (ip, true, Some(mac)));
+        let ping_hosts = ping_results
+            .into_iter()
+            .flatten()
+            .map(|(ip, is_up, _target)| (ip, is_up, None));
+        for (ip, is_up, link_mac) in link_hosts.chain(ping_hosts) {
             if is_up {
                 // 只有在非UI模式下才打印主机在线信息
                 if !self.use_ui {
-                    println!("Host {} is up", ip);
+                    if link_mac.is_some() {
+                        let via = if ip.is_ipv4() { "arp" } else { "ndp" };
+                        println!("Host {} is up ({})", ip, via);
+                    } else {
+                        println!("Host {} is up", ip);
+                    }
                 } else {
                     // eprintln!("UI mode: Host {} is up", ip);
                 }
 
-                // 创建异步任务来获取设备信息
-                let mac_future = if self.resolve_mac {
+                // 创建异步任务来获取设备信息，ARP或邻居发现已经得到MAC时不再查询
+                let mac_future = if self.resolve_mac && link_mac.is_none() {
                     Some(self.get_mac_address(ip))
                 } else {
                     None
                 };
 
-                let hostname_future = self.resolve_hostname(ip);
+                let hostname_future = resolve_hostname(ip);
 
                 // 并行获取MAC地址和主机名
                 let (mac, hostname) = match (mac_future, hostname_future) {
@@ -354,6 +409,7 @@ impl NetworkMonitor {
                         (None, hostname_res)
                     }
                 };
+                let mac = link_mac.or(mac);
 
                 let vendor = if let Some(ref mac_addr) = mac {
                     self.lookup_vendor(mac_addr)
@@ -607,6 +663,102 @@ impl NetworkMonitor {
         println!();
     }
 
+    /// 对直连网段中的地址做一次ARP扫描，返回应答的地址和MAC
+    ///
+    /// 没有直连网卡或权限不足时返回空表，此时所有地址都用ping探测
+    async fn arp_sweep(&mut self) -> HashMap<IpAddr, String> {
+        let arp = match &self.arp {
+            Some(arp) => arp.clone(),
+            None => return HashMap::new(),
+        };
+        let targets: Vec<_> = self
+            .network
+            .iter()
+            .filter_map(|ip| match ip {
+                IpAddr::V4(ip) if arp.is_local(ip) && !ip.is_loopback() => Some(ip),
+                _ => None,
+            })
+            .collect();
+        if targets.is_empty() {
+            return HashMap::new();
+        }
+
+        let result =
+            task::spawn_blocking(move || arp::sweep(&arp, &targets, Duration::from_millis(500)))
+                .await;
+        match result {
+            Ok(Ok(found)) => found
+                .into_iter()
+                .map(|(ip, mac)| (IpAddr::V4(ip), mac.to_string()))
+                .collect(),
+            Ok(Err(e)) => {
+                // 之后的扫描不再尝试ARP
+                if !self.use_ui {
+                    println!("ARP discovery unavailable, falling back to ping: {}", e);
+                }
+                self.arp = None;
+                HashMap::new()
+            }
+            Err(_) => HashMap::new(),
+        }
+    }
+
+    /// 网段是否小到可以逐个地址ping：IPv4总是可以，IPv6只限/112及更小的网段
+    fn is_enumerable(&self) -> bool {
+        match self.network {
+            IpNetwork::V4(_) => true,
+            IpNetwork::V6(network) => network.prefix() >= 112,
+        }
+    }
+
+    /// 对IPv6网段做一次邻居发现，返回网段内应答的地址和MAC
+    ///
+    /// 已知的设备（以及/112及更小网段中的所有地址）会单独发送邻居请求，
+    /// 不回应组播ping的设备消失后不会被误判为离线
+    async fn ndp_discover(&mut self) -> HashMap<IpAddr, String> {
+        let ndp = match &self.ndp {
+            Some(ndp) => ndp.clone(),
+            None => return HashMap::new(),
+        };
+        let network = self.network;
+        let mut targets: HashSet<Ipv6Addr> = self
+            .devices
+            .keys()
+            .filter_map(|ip| match ip {
+                IpAddr::V6(ip) => Some(*ip),
+                _ => None,
+            })
+            .collect();
+        if self.is_enumerable() {
+            targets.extend(network.iter().filter_map(|ip| match ip {
+                IpAddr::V6(ip) => Some(ip),
+                _ => None,
+            }));
+        }
+        let targets: Vec<_> = targets.into_iter().collect();
+
+        let result = task::spawn_blocking(move || {
+            ndp::discover(&ndp, &targets, Duration::from_millis(1000))
+        })
+        .await;
+        match result {
+            Ok(Ok(found)) => found
+                .into_iter()
+                .map(|(ip, mac)| (IpAddr::V6(ip), mac.to_string()))
+                .filter(|(ip, _)| network.contains(*ip))
+                .collect(),
+            Ok(Err(e)) => {
+                // 之后的扫描不再尝试邻居发现
+                if !self.use_ui {
+                    println!("IPv6 neighbor discovery unavailable: {}", e);
+                }
+                self.ndp = None;
+                HashMap::new()
+            }
+            Err(_) => HashMap::new(),
+        }
+    }
+
     async fn get_mac_address(&self, ip: IpAddr) -> Option<String> {
         // 使用系统命令获取MAC地址
         // 在Linux上使用arp命令，在macOS上也可以使用arp命令
@@ -644,35 +796,6 @@ impl NetworkMonitor {
         }
     }
 
-    async fn resolve_hostname(&self, ip: IpAddr) -> Option<String> {
-        // 使用反向DNS查询获取主机名
-        match tokio::process::Command::new("host")
-            .arg(ip.to_string())
-            .output()
-            .await
-        {
-            Ok(output) => {
-                if output.status.success() {
-                    let output_str = String::from_utf8_lossy(&output.stdout);
-
-                    // 解析host命令输出，提取主机名
-                    // 格式通常是: IP地址 domain name pointer hostname.
-                    for line in output_str.lines() {
-                        if line.contains("domain name pointer") {
-                            let parts: Vec<&str> = line.split("domain name pointer").collect();
-                            if parts.len() >= 2 {
-                                let hostname = parts[1].trim().trim_end_matches('.');
-                                return Some(hostname.to_string());
-                            }
-                        }
-                    }
-                }
-                None
-            }
-            Err(_) => None,
-        }
-    }
-
     fn lookup_vendor(&self, mac: &str) -> Option<String> {
         // 简化实现：根据MAC地址前缀判断厂商
         // 实际应用中应该使用MAC地址厂商数据库
@@ -0,0 +1,154 @@
+use futures::future::join_all;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time;
+
+use crate::error::PingError;
+use crate::host::{resolve_hostname, PingTarget};
+use crate::pinger::Pinger;
+use crate::socket::Binding;
+use crate::stats::PingStats;
+use crate::trace;
+
+/// 持续监测中的一跳
+#[derive(Debug, Clone)]
+pub struct MtrHop {
+    /// 跳数（即探测使用的TTL）
+    pub ttl: u32,
+    /// 这一跳出现过的应答者，按首次出现的顺序排列
+    pub addrs: Vec<IpAddr>,
+    /// 第一个应答者的反向解析名称
+    pub hostname: Option<String>,
+    /// 这一跳的丢包和RTT统计
+    pub stats: PingStats,
+}
+
+/// 持续监测的结果，每轮探测后更新
+#[derive(Debug, Clone)]
+pub struct MtrReport {
+    pub target: PingTarget,
+    pub size: usize,
+    /// 已完成的探测轮数
+    pub cycles: u32,
+    pub hops: Vec<MtrHop>,
+}
+
+/// 先做一次traceroute确定路径长度，然后按固定间隔持续探测每一跳
+///
+/// 每一轮对所有跳并行发送一个探测，收到Echo Reply或Time Exceeded记为成功，
+/// 超时记为丢包，这样可以看出丢包是从哪一跳开始的
+///
+/// # 参数
+///
+/// * `target`: 目标主机
+/// * `max_hops`: 最大跳数
+/// * `cycles`: 探测轮数，0表示一直运行到`running`被置为false
+/// * `interval_ms`: 每轮探测之间的间隔时间（毫秒）
+/// * `timeout_ms`: 每个探测的超时时间（毫秒）
+/// * `size`: ICMP包的大小
+/// * `binding`: 绑定的源地址和网卡
+/// * `resolve`: 是否对每一跳做反向DNS解析
+/// * `running`: 运行状态，被置为false时在本轮结束后停止
+/// * `on_cycle`: 每完成一轮时调用，用于刷新显示
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    target: PingTarget,
+    max_hops: u32,
+    cycles: u32,
+    interval_ms: u64,
+    timeout_ms: u64,
+    size: usize,
+    binding: &Binding,
+    resolve: bool,
+    running: Arc<Mutex<bool>>,
+    mut on_cycle: impl FnMut(&MtrReport),
+) -> Result<MtrReport, PingError> {
+    let discovered = trace::trace(
+        target.clone(),
+        max_hops,
+        1,
+        timeout_ms,
+        size,
+        binding,
+        false,
+        |_| {},
+    )
+    .await?;
+    let hop_count = if discovered.reached {
+        discovered.hops.len() as u32
+    } else {
+        // 未到达目标时探测到最后一个有应答的跳之后一跳，便于观察路径在哪里中断
+        let last_answered = discovered
+            .hops
+            .iter()
+            .rev()
+            .find(|hop| !hop.addrs.is_empty())
+            .map_or(0, |hop| hop.ttl);
+        (last_answered + 1).min(max_hops)
+    };
+
+    let mut pingers = Vec::new();
+    let mut hops = Vec::new();
+    for ttl in 1..=hop_count {
+        pingers.push(Pinger::new(target.clone(), size, ttl, binding)?);
+        hops.push(MtrHop {
+            ttl,
+            addrs: Vec::new(),
+            hostname: None,
+            stats: PingStats::new(),
+        });
+    }
+
+    let mut report = MtrReport {
+        target,
+        size,
+        cycles: 0,
+        hops,
+    };
+    on_cycle(&report);
+
+    while *running.lock().unwrap() && (cycles == 0 || report.cycles < cycles) {
+        let started = Instant::now();
+        let seq = report.cycles as u16;
+        let responses = join_all(
+            pingers
+                .iter()
+                .map(|pinger| pinger.ping_once(seq, timeout_ms)),
+        )
+        .await;
+
+        for (hop, response) in report.hops.iter_mut().zip(responses) {
+            let responder = response.responder();
+            match (responder, response.rtt) {
+                (Some(responder), Some(rtt)) => {
+                    hop.stats.update_with_success(seq, rtt);
+                    if !hop.addrs.contains(&responder) {
+                        hop.addrs.push(responder);
+                        if resolve && hop.hostname.is_none() {
+                            hop.hostname = resolve_hostname(responder).await;
+                        }
+                    }
+                }
+                _ => {
+                    let error = response.error.unwrap_or(PingError::Timeout);
+                    hop.stats.update_with_failure(seq, &error);
+                }
+            }
+        }
+
+        report.cycles += 1;
+        on_cycle(&report);
+
+        if cycles != 0 && report.cycles >= cycles {
+            break;
+        }
+        let elapsed = started.elapsed();
+        let interval = Duration::from_millis(interval_ms);
+        if elapsed < interval {
+            time::sleep(interval - elapsed).await;
+        }
+    }
+
+    Ok(report)
+}
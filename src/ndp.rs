@@ -0,0 +1,268 @@
+use ipnetwork::IpNetwork;
+use pnet::datalink::{self, Channel, Config, MacAddr, NetworkInterface};
+use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
+use pnet::packet::icmpv6::{self, Icmpv6Packet, Icmpv6Types, MutableIcmpv6Packet};
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv6::{Ipv6Packet, MutableIpv6Packet};
+use pnet::packet::{MutablePacket, Packet};
+use rand::random;
+use std::collections::HashMap;
+use std::io;
+use std::net::Ipv6Addr;
+use std::time::{Duration, Instant};
+
+use crate::error::PingError;
+use crate::link::DirectLink;
+use crate::pacer;
+use crate::socket::Binding;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const IPV6_HEADER_LEN: usize = 40;
+
+/// 所有节点的组播地址
+const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// NDP选项类型：源/目标链路层地址
+const OPTION_SOURCE_LL: u8 = 1;
+const OPTION_TARGET_LL: u8 = 2;
+
+/// 与目标网段直连的网卡，组播Echo Request和邻居请求从这里发出
+#[derive(Debug, Clone)]
+pub struct NdpInterface {
+    pub interface: NetworkInterface,
+    /// 网卡在该网段上的IPv6地址，作为探测的源地址
+    pub source_ip: Ipv6Addr,
+    pub source_mac: MacAddr,
+}
+
+impl NdpInterface {
+    /// 查找与`network`有交集的、已启用的非回环以太网卡
+    ///
+    /// 指定了网卡或源地址时，只使用该网卡或拥有该地址的网卡
+    pub fn for_network(network: &IpNetwork, binding: &Binding) -> Option<Self> {
+        if !network.is_ipv6() {
+            return None;
+        }
+        let link = DirectLink::for_network(network, binding)?;
+        match link.subnet {
+            IpNetwork::V6(subnet) => Some(Self {
+                interface: link.interface,
+                source_ip: subnet.ip(),
+                source_mac: link.source_mac,
+            }),
+            IpNetwork::V4(_) => None,
+        }
+    }
+}
+
+/// 向ff02::1发送Echo Request，并向`targets`发送邻居请求，
+/// 收集Echo Reply和邻居通告的发送方地址及其MAC
+///
+/// 不回应组播ping的主机（如Windows）仍必须回应邻居请求，所以已知的地址都会再单独请求一次。
+/// 阻塞调用，应放在`spawn_blocking`中执行；需要root或CAP_NET_RAW权限
+pub fn discover(
+    ndp: &NdpInterface,
+    targets: &[Ipv6Addr],
+    timeout: Duration,
+) -> Result<HashMap<Ipv6Addr, MacAddr>, PingError> {
+    let config = Config {
+        read_timeout: Some(Duration::from_millis(50)),
+        ..Default::default()
+    };
+    let (mut tx, mut rx) = match datalink::channel(&ndp.interface, config) {
+        Ok(Channel::Ethernet(tx, rx)) => (tx, rx),
+        Ok(_) => return Err(PingError::Other("unsupported datalink channel".to_string())),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            return Err(PingError::PermissionDenied)
+        }
+        Err(e) => return Err(PingError::SendError(e)),
+    };
+
+    let identifier = random::<u16>();
+    let mut frames = vec![build_echo_request(
+        ndp.source_mac,
+        ndp.source_ip,
+        identifier,
+    )];
+    frames.extend(
+        targets
+            .iter()
+            .map(|target| build_neighbor_solicitation(ndp.source_mac, ndp.source_ip, *target)),
+    );
+    for frame in &frames {
+        pacer::wait_blocking();
+        if let Some(Err(e)) = tx.send_to(frame, None) {
+            return Err(PingError::SendError(e));
+        }
+    }
+
+    let mut found = HashMap::new();
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        match rx.next() {
+            Ok(frame) => {
+                if let Some((ip, mac)) = parse_response(frame, identifier) {
+                    if ip != ndp.source_ip {
+                        found.insert(ip, mac);
+                    }
+                }
+            }
+            // 读超时，继续等待直到截止时间
+            Err(_) => continue,
+        }
+    }
+
+    Ok(found)
+}
+
+/// 请求节点组播地址 ff02::1:ffXX:XXXX
+pub fn solicited_node(target: Ipv6Addr) -> Ipv6Addr {
+    let octets = target.octets();
+    Ipv6Addr::new(
+        0xff02,
+        0,
+        0,
+        0,
+        0,
+        1,
+        0xff00 | octets[13] as u16,
+        u16::from_be_bytes([octets[14], octets[15]]),
+    )
+}
+
+/// IPv6组播地址对应的以太网地址 33:33:XX:XX:XX:XX
+fn multicast_mac(group: Ipv6Addr) -> MacAddr {
+    let octets = group.octets();
+    MacAddr::new(0x33, 0x33, octets[12], octets[13], octets[14], octets[15])
+}
+
+fn build_echo_request(source_mac: MacAddr, source: Ipv6Addr, identifier: u16) -> Vec<u8> {
+    let mut icmp = vec![0u8; 8];
+    icmp[0] = Icmpv6Types::EchoRequest.0;
+    icmp[4..6].copy_from_slice(&identifier.to_be_bytes());
+    build_frame(source_mac, source, ALL_NODES, icmp)
+}
+
+fn build_neighbor_solicitation(source_mac: MacAddr, source: Ipv6Addr, target: Ipv6Addr) -> Vec<u8> {
+    // 类型、代码、校验和、保留字段、目标地址，以及源链路层地址选项
+    let mut icmp = vec![0u8; 32];
+    icmp[0] = Icmpv6Types::NeighborSolicit.0;
+    icmp[8..24].copy_from_slice(&target.octets());
+    icmp[24] = OPTION_SOURCE_LL;
+    icmp[25] = 1;
+    icmp[26..32].copy_from_slice(&source_mac.octets());
+    build_frame(source_mac, source, solicited_node(target), icmp)
+}
+
+/// 组装以太网帧，填写ICMPv6校验和；NDP要求跳数限制为255
+fn build_frame(
+    source_mac: MacAddr,
+    source: Ipv6Addr,
+    destination: Ipv6Addr,
+    mut icmp: Vec<u8>,
+) -> Vec<u8> {
+    let checksum = icmpv6::checksum(&Icmpv6Packet::new(&icmp).unwrap(), &source, &destination);
+    MutableIcmpv6Packet::new(&mut icmp)
+        .unwrap()
+        .set_checksum(checksum);
+
+    let mut frame = vec![0u8; ETHERNET_HEADER_LEN + IPV6_HEADER_LEN + icmp.len()];
+    let mut ethernet = MutableEthernetPacket::new(&mut frame).unwrap();
+    ethernet.set_destination(multicast_mac(destination));
+    ethernet.set_source(source_mac);
+    ethernet.set_ethertype(EtherTypes::Ipv6);
+
+    let mut ipv6 = MutableIpv6Packet::new(ethernet.payload_mut()).unwrap();
+    ipv6.set_version(6);
+    ipv6.set_payload_length(icmp.len() as u16);
+    ipv6.set_next_header(IpNextHeaderProtocols::Icmpv6);
+    ipv6.set_hop_limit(255);
+    ipv6.set_source(source);
+    ipv6.set_destination(destination);
+    ipv6.set_payload(&icmp);
+
+    frame
+}
+
+/// 解析本次探测的Echo Reply或任意邻居通告，返回应答方的(IP, MAC)
+pub fn parse_response(frame: &[u8], identifier: u16) -> Option<(Ipv6Addr, MacAddr)> {
+    let ethernet = EthernetPacket::new(frame)?;
+    if ethernet.get_ethertype() != EtherTypes::Ipv6 {
+        return None;
+    }
+    let ipv6 = Ipv6Packet::new(ethernet.payload())?;
+    if ipv6.get_next_header() != IpNextHeaderProtocols::Icmpv6 {
+        return None;
+    }
+    let icmp = ipv6.payload();
+    let icmp_type = *icmp.first()?;
+
+    if icmp_type == Icmpv6Types::EchoReply.0 {
+        if icmp.get(4..6)? != identifier.to_be_bytes() {
+            return None;
+        }
+        return Some((ipv6.get_source(), ethernet.get_source()));
+    }
+
+    if icmp_type == Icmpv6Types::NeighborAdvert.0 {
+        let target: [u8; 16] = icmp.get(8..24)?.try_into().ok()?;
+        // 优先使用目标链路层地址选项，没有时使用以太网源地址
+        let mut mac = ethernet.get_source();
+        let mut options = icmp.get(24..)?;
+        while options.len() >= 8 {
+            let len = options[1] as usize * 8;
+            if len == 0 || len > options.len() {
+                break;
+            }
+            if options[0] == OPTION_TARGET_LL {
+                mac = MacAddr::new(
+                    options[2], options[3], options[4], options[5], options[6], options[7],
+                );
+            }
+            options = &options[len..];
+        }
+        return Some((Ipv6Addr::from(target), mac));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solicited_node_address() {
+        let target: Ipv6Addr = "2001:db8::1:2:3".parse().unwrap();
+        assert_eq!(
+            solicited_node(target),
+            "ff02::1:ff02:3".parse::<Ipv6Addr>().unwrap()
+        );
+        assert_eq!(
+            multicast_mac(solicited_node(target)).to_string(),
+            "33:33:ff:02:00:03"
+        );
+    }
+
+    #[test]
+    fn test_parses_neighbor_advertisement() {
+        let router_mac = MacAddr::new(0x02, 0, 0, 0, 0, 1);
+        let target_mac = MacAddr::new(0x52, 0x54, 0, 0xab, 0xcd, 0xef);
+        let target: Ipv6Addr = "2001:db8::20".parse().unwrap();
+
+        let mut icmp = vec![0u8; 32];
+        icmp[0] = Icmpv6Types::NeighborAdvert.0;
+        icmp[8..24].copy_from_slice(&target.octets());
+        icmp[24] = OPTION_TARGET_LL;
+        icmp[25] = 1;
+        icmp[26..32].copy_from_slice(&target_mac.octets());
+        let frame = build_frame(router_mac, target, ALL_NODES, icmp);
+
+        assert_eq!(parse_response(&frame, 0), Some((target, target_mac)));
+
+        // 自己发出的邻居请求不算应答
+        let solicitation =
+            build_neighbor_solicitation(router_mac, target, "2001:db8::1".parse().unwrap());
+        assert_eq!(parse_response(&solicitation, 0), None);
+    }
+}
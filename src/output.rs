@@ -1,16 +1,25 @@
 use chrono::Local;
 use colored::Colorize;
+use std::net::SocketAddr;
 use std::time::Duration;
 
-use crate::host::PingResponse;
+use crate::host::{PingResponse, Probe};
+use crate::icmp::{IcmpErrorKind, PayloadCheck};
+use crate::mtr::{MtrHop, MtrReport};
+use crate::pmtu::{PmtuResult, PmtuStep, ProbeOutcome};
 use crate::stats::PingStats;
+use crate::trace::{Hop, TraceResult};
+use crate::tracker::ReplyClass;
+
+/// CSV摘要的固定列，后面依次是RTT标准差、抖动和所选的各个百分位
+const CSV_HEADER: &str = "host,packets_transmitted,packets_received,packet_loss_percent,rtt_min_ms,rtt_avg_ms,rtt_max_ms,net_unreachable,host_unreachable,port_unreachable,admin_prohibited,ttl_exceeded,frag_needed,corrupted,truncated,duplicates,late,out_of_order,rtt_source,port_closed,clock_offset_ms,forward_plus_offset_ms,return_minus_offset_ms,dscp,probes_sent,probes_answered,probe_success_percent,retries,probes_retried";
 
 pub fn format_duration(duration: Duration) -> String {
     let millis = duration.as_millis();
     if millis < 1 {
         format!("{} µs", duration.as_micros())
     } else if millis < 1000 {
-        format!("{:.2} ms", millis as f64)
+        format!("{:.2} ms", duration.as_secs_f64() * 1000.0)
     } else {
         format!("{:.2} s", duration.as_secs_f64())
     }
@@ -20,6 +29,25 @@ pub fn print_ping_start(host: &str, addr: &str, size: usize) {
     println!("PING {} ({}): {} data bytes", host, addr, size);
 }
 
+pub fn print_tcp_start(host: &str, addr: &str, port: u16) {
+    println!("TCP PING {} ({}): port {}", host, addr, port);
+}
+
+pub fn print_timestamp_start(host: &str, addr: &str) {
+    println!("ICMP TIMESTAMP {} ({})", host, addr);
+}
+
+pub fn print_udp_start(host: &str, addr: &str, port: u16, size: usize) {
+    println!(
+        "UDP PING {} ({}) port {}: {} data bytes",
+        host, addr, port, size
+    );
+}
+
+pub fn print_responder_start(addr: &SocketAddr) {
+    println!("UDP responder listening on {}", addr);
+}
+
 pub fn print_ping_result(response: &PingResponse, show_timestamp: bool) {
     let timestamp_str = if show_timestamp {
         format!("[{}] ", Local::now().format("%H:%M:%S%.3f"))
@@ -27,28 +55,115 @@ pub fn print_ping_result(response: &PingResponse, show_timestamp: bool) {
         String::new()
     };
 
-    match &response.error {
-        None => {
+    // 同一主机按多个DSCP探测时，每行都要标明所属的类别
+    let mut class = response
+        .target
+        .dscp
+        .map_or(String::new(), |dscp| format!(" dscp={}", dscp));
+    // 重试使用新的线路序号，序号与逻辑探测的编号不再一致时标明所属的探测
+    if let Some(probe_seq) = response.probe_seq {
+        if response.attempt > 0 || probe_seq != response.seq as u64 {
+            class.push_str(&format!(" probe={}", probe_seq));
+        }
+    }
+    if response.attempt > 0 {
+        class.push_str(&format!(" retry={}", response.attempt));
+    }
+
+    match (&response.error, response.probe) {
+        (None, Probe::Tcp { port, open }) => {
+            let state = if open {
+                "open".green()
+            } else {
+                "closed (RST)".yellow()
+            };
+            println!(
+                "{}Port {} {} on {}: tcp_seq={} time={}",
+                timestamp_str,
+                port,
+                state,
+                response.target.addr,
+                response.seq,
+                format_duration(response.rtt.unwrap()).green()
+            );
+        }
+        (None, _) => {
             let rtt = response.rtt.unwrap();
+            let mut integrity = match response.payload_check {
+                PayloadCheck::Intact => String::new(),
+                PayloadCheck::Truncated => format!(" {}", "(truncated)".red()),
+                PayloadCheck::Corrupted => format!(" {}", "(wrong data)".red()),
+            };
+            match response.class {
+                ReplyClass::OnTime => {}
+                ReplyClass::OutOfOrder => {
+                    integrity.push_str(&format!(" {}", "(out of order)".yellow()))
+                }
+                ReplyClass::Late => integrity.push_str(&format!(" {}", "(late)".yellow())),
+                ReplyClass::Duplicate => integrity.push_str(&format!(" {}", "(DUP!)".yellow())),
+            }
+            match response.probe {
+                Probe::Timestamp { clock: Some(clock) } => integrity.push_str(&format!(
+                    " offset={:+.1} ms fwd+offset={} ms ret-offset={} ms",
+                    clock.offset_ms(),
+                    clock.forward_ms,
+                    clock.return_ms
+                )),
+                Probe::Timestamp { clock: None } => {
+                    integrity.push_str(&format!(" {}", "(non-standard time)".yellow()))
+                }
+                _ => {}
+            }
+            // UDP回显读不到回复的TTL
+            let (from, ttl) = match response.probe {
+                Probe::Udp { port } => (
+                    SocketAddr::new(response.target.addr, port).to_string(),
+                    String::new(),
+                ),
+                _ => (
+                    response.target.addr.to_string(),
+                    format!(" ttl={}", response.ttl),
+                ),
+            };
             println!(
-                "{}{} bytes from {}: icmp_seq={} ttl={} time={}",
+                "{}{} bytes from {}: {}={}{}{} time={}{}",
                 timestamp_str,
                 response.bytes,
-                response.target.addr,
+                from,
+                response.probe.seq_label(),
                 response.seq,
-                response.ttl,
-                format_duration(rtt).green()
+                ttl,
+                class,
+                format_duration(rtt).green(),
+                integrity
             );
         }
-        Some(crate::error::PingError::Timeout) => {
+        (Some(crate::error::PingError::Timeout), _) => {
             println!(
-                "{}Request timeout for icmp_seq={} ({})",
+                "{}Request timeout for {}={}{} ({})",
                 timestamp_str,
+                response.probe.seq_label(),
                 response.seq,
+                class,
                 response.target.addr.to_string().red()
             );
         }
-        Some(e) => {
+        (Some(e), _) if e.icmp_error_kind().is_some() => {
+            let mut description = e.icmp_error_kind().unwrap().description().to_string();
+            if let crate::error::PingError::FragmentationNeeded { mtu, .. } = e {
+                description.push_str(&format!(" (mtu = {})", mtu));
+            }
+            println!(
+                "{}From {} {}={}{} {}",
+                timestamp_str,
+                e.router().unwrap(),
+                response.probe.seq_label(),
+                response.seq,
+                class,
+                description.red()
+            );
+        }
+        (Some(e), _) => {
             println!(
                 "{}Error pinging {} (seq={}): {}",
                 timestamp_str,
@@ -60,60 +175,628 @@ pub fn print_ping_result(response: &PingResponse, show_timestamp: bool) {
     }
 }
 
-pub fn print_ping_summary(host: &str, stats: &PingStats) {
+/// CSV摘要的表头，多个主机时只输出一次
+pub fn csv_header(percentiles: &[f64]) -> String {
+    let mut header = format!("{},rtt_stddev_ms,jitter_ms", CSV_HEADER);
+    for percentile in percentiles {
+        header.push_str(&format!(",rtt_p{}_ms", percentile));
+    }
+    header
+}
+
+/// 毫秒数，没有样本时为空
+fn optional_ms(duration: Option<Duration>, precision: usize) -> Option<String> {
+    duration.map(|d| format!("{:.*}", precision, d.as_secs_f64() * 1000.0))
+}
+
+pub fn print_ping_summary(host: &str, stats: &PingStats, percentiles: &[f64]) {
     println!("\n--- {} ping statistics ---", host);
+    let mut received = format!("{} received", stats.received);
+    if stats.duplicates > 0 {
+        received.push_str(&format!(", +{} duplicates", stats.duplicates));
+    }
+    if stats.late > 0 {
+        received.push_str(&format!(", +{} late", stats.late));
+    }
+    if stats.corrupted > 0 {
+        received.push_str(&format!(", +{} corrupted", stats.corrupted));
+    }
+    if stats.truncated > 0 {
+        received.push_str(&format!(", +{} truncated", stats.truncated));
+    }
     println!(
-        "{} packets transmitted, {} received, {:.1}% packet loss",
+        "{} packets transmitted, {}, {:.1}% packet loss",
         stats.sent,
-        stats.received,
+        received,
         stats.loss_percent()
     );
 
+    // 有重试时数据包的丢失率不等于探测的失败率，单独列出
+    if stats.retries > 0 {
+        println!(
+            "{} probes, {} answered, {:.1}% probe success, {} retries ({} probes retried)",
+            stats.probes,
+            stats.probes_answered,
+            stats.probe_success_percent(),
+            stats.retries,
+            stats.probes_retried
+        );
+    }
+
+    if stats.port_closed > 0 {
+        println!(
+            "{} of {} replies were RST (host up, port closed)",
+            stats.port_closed, stats.received
+        );
+    }
+
+    if !stats.icmp_errors.is_empty() {
+        let errors: Vec<String> = IcmpErrorKind::ALL
+            .iter()
+            .filter(|kind| stats.icmp_error_count(**kind) > 0)
+            .map(|kind| format!("{}={}", kind.name(), stats.icmp_error_count(*kind)))
+            .collect();
+        println!("icmp errors: {}", errors.join(", "));
+    }
+
+    if stats.out_of_order > 0 {
+        println!("{} replies out of order", stats.out_of_order);
+    }
+
     if stats.received > 0 {
         println!(
-            "rtt min/avg/max = {}/{}/{}",
+            "rtt min/avg/max/mdev = {}/{}/{}/{}",
             format_duration(stats.min_rtt.unwrap()),
             format_duration(stats.avg_rtt().unwrap()),
-            format_duration(stats.max_rtt.unwrap())
+            format_duration(stats.max_rtt.unwrap()),
+            format_duration(stats.stddev_rtt().unwrap())
+        );
+
+        if !percentiles.is_empty() {
+            let labels: Vec<String> = percentiles.iter().map(|p| format!("p{}", p)).collect();
+            let values: Vec<String> = percentiles
+                .iter()
+                .map(|p| format_duration(stats.percentile_rtt(*p).unwrap()))
+                .collect();
+            println!("rtt {} = {}", labels.join("/"), values.join("/"));
+        }
+
+        if let Some(jitter) = stats.jitter() {
+            println!("jitter = {}", format_duration(jitter));
+        }
+
+        // 单独成行，上面的rtt行保持与iputils一致，便于脚本解析
+        println!("rtt source: {} timestamps", stats.rtt_source());
+    }
+
+    if let Some(offset) = stats.clock_offset_ms() {
+        println!(
+            "clock offset = {:+.1} ms, fwd+offset/ret-offset = {:.1}/{:.1} ms ({} samples)",
+            offset,
+            stats.forward_delay_ms().unwrap(),
+            stats.return_delay_ms().unwrap(),
+            stats.clock_samples
         );
     }
 }
 
-pub fn print_json_summary(host: &str, stats: &PingStats) -> String {
+pub fn print_json_summary(host: &str, stats: &PingStats, percentiles: &[f64]) -> String {
     let min = stats.min_rtt.map_or(0.0, |d| d.as_secs_f64() * 1000.0);
     let avg = stats.avg_rtt().map_or(0.0, |d| d.as_secs_f64() * 1000.0);
     let max = stats.max_rtt.map_or(0.0, |d| d.as_secs_f64() * 1000.0);
 
+    let icmp_errors: Vec<String> = IcmpErrorKind::ALL
+        .iter()
+        .map(|kind| format!("    \"{}\": {}", kind.name(), stats.icmp_error_count(*kind)))
+        .collect();
+
+    // 没有回复时为null
+    let mut rtt_extra = vec![
+        format!(
+            "    \"stddev\": {}",
+            optional_ms(stats.stddev_rtt(), 3).unwrap_or("null".to_string())
+        ),
+        format!(
+            "    \"jitter\": {}",
+            optional_ms(stats.jitter(), 3).unwrap_or("null".to_string())
+        ),
+    ];
+    for percentile in percentiles {
+        rtt_extra.push(format!(
+            "    \"p{}\": {}",
+            percentile,
+            optional_ms(stats.percentile_rtt(*percentile), 3).unwrap_or("null".to_string())
+        ));
+    }
+
+    // 没有时间戳应答时为null；去程和回程时延无法与时钟偏差分开，按含偏差的原始值输出
+    let clock = match stats.clock_offset_ms() {
+        Some(offset) => format!(
+            r#"{{
+    "offset": {:.1},
+    "forward_plus_offset": {:.1},
+    "return_minus_offset": {:.1},
+    "samples": {}
+  }}"#,
+            offset,
+            stats.forward_delay_ms().unwrap(),
+            stats.return_delay_ms().unwrap(),
+            stats.clock_samples
+        ),
+        None => "null".to_string(),
+    };
+
     format!(
         r#"{{
   "host": "{}",
+  "source": {},
+  "dscp": {},
   "packets_transmitted": {},
   "packets_received": {},
+  "packets_corrupted": {},
+  "packets_truncated": {},
+  "packets_duplicate": {},
+  "packets_late": {},
+  "packets_out_of_order": {},
+  "packets_port_closed": {},
   "packet_loss_percent": {:.1},
+  "probes_sent": {},
+  "probes_answered": {},
+  "probe_success_percent": {:.1},
+  "retries": {},
+  "probes_retried": {},
   "rtt_ms": {{
     "min": {:.3},
     "avg": {:.3},
-    "max": {:.3}
+    "max": {:.3},
+{}
+  }},
+  "rtt_source": "{}",
+  "clock_ms": {},
+  "icmp_errors": {{
+{}
   }}
 }}"#,
         host,
+        stats
+            .source
+            .map_or("null".to_string(), |source| format!("\"{}\"", source)),
+        stats
+            .dscp
+            .map_or("null".to_string(), |dscp| dscp.to_string()),
         stats.sent,
         stats.received,
+        stats.corrupted,
+        stats.truncated,
+        stats.duplicates,
+        stats.late,
+        stats.out_of_order,
+        stats.port_closed,
         stats.loss_percent(),
+        stats.probes,
+        stats.probes_answered,
+        stats.probe_success_percent(),
+        stats.retries,
+        stats.probes_retried,
         min,
         avg,
-        max
+        max,
+        rtt_extra.join(",\n"),
+        stats.rtt_source(),
+        clock,
+        icmp_errors.join(",\n")
     )
 }
 
-pub fn print_csv_summary(host: &str, stats: &PingStats) -> String {
+pub fn print_csv_summary(host: &str, stats: &PingStats, percentiles: &[f64]) -> String {
     let min = stats.min_rtt.map_or(0.0, |d| d.as_secs_f64() * 1000.0);
     let avg = stats.avg_rtt().map_or(0.0, |d| d.as_secs_f64() * 1000.0);
     let max = stats.max_rtt.map_or(0.0, |d| d.as_secs_f64() * 1000.0);
 
+    let icmp_errors: Vec<String> = IcmpErrorKind::ALL
+        .iter()
+        .map(|kind| stats.icmp_error_count(*kind).to_string())
+        .collect();
+
+    let clock: Vec<String> = [
+        stats.clock_offset_ms(),
+        stats.forward_delay_ms(),
+        stats.return_delay_ms(),
+    ]
+    .iter()
+    .map(|value| value.map_or(String::new(), |ms| format!("{:.1}", ms)))
+    .collect();
+
+    let mut latency = vec![
+        optional_ms(stats.stddev_rtt(), 3).unwrap_or_default(),
+        optional_ms(stats.jitter(), 3).unwrap_or_default(),
+    ];
+    for percentile in percentiles {
+        latency.push(optional_ms(stats.percentile_rtt(*percentile), 3).unwrap_or_default());
+    }
+
+    format!(
+        "{}\n{},{},{},{:.1},{:.3},{:.3},{:.3},{},{},{},{},{},{},{},{},{},{},{},{},{:.1},{},{},{}",
+        csv_header(percentiles),
+        host,
+        stats.sent,
+        stats.received,
+        stats.loss_percent(),
+        min,
+        avg,
+        max,
+        icmp_errors.join(","),
+        stats.corrupted,
+        stats.truncated,
+        stats.duplicates,
+        stats.late,
+        stats.out_of_order,
+        stats.rtt_source(),
+        stats.port_closed,
+        clock.join(","),
+        stats.dscp.map_or(String::new(), |dscp| dscp.to_string()),
+        stats.probes,
+        stats.probes_answered,
+        stats.probe_success_percent(),
+        stats.retries,
+        stats.probes_retried,
+        latency.join(",")
+    )
+}
+
+/// 与fping的sprint_tm相同的毫秒格式，按数量级保留不同的小数位数
+pub fn format_fping_ms(duration: Duration) -> String {
+    let ms = duration.as_secs_f64() * 1000.0;
+    if ms < 1.0 {
+        format!("{:.3}", ms)
+    } else if ms < 10.0 {
+        format!("{:.2}", ms)
+    } else if ms < 100.0 {
+        format!("{:.1}", ms)
+    } else if ms < 1_000_000.0 {
+        format!("{:.0}", ms)
+    } else {
+        format!("{:.3e}", ms)
+    }
+}
+
+/// 以fping -C的格式输出一个样本：
+/// `host : [0], 64 bytes, 0.45 ms (0.45 avg, 0% loss)`，超时为`host : [1], timed out (0.45 avg, 50% loss)`
+///
+/// 与fping相同，收到ICMP差错时先向stderr输出
+/// `ICMP Host Unreachable from 10.0.0.1 for ICMP Echo sent to host`，该探测仍记为超时
+pub fn print_fping_sample(
+    name: &str,
+    width: usize,
+    index: usize,
+    response: &PingResponse,
+    stats: &PingStats,
+) {
+    if let Some(error) = &response.error {
+        if let (Some(kind), Some(router)) = (error.icmp_error_kind(), error.router()) {
+            eprintln!(
+                "{} from {} for ICMP Echo sent to {}",
+                kind.fping_description(),
+                router,
+                name
+            );
+        }
+    }
+    let result = match (&response.error, response.rtt) {
+        (None, Some(rtt)) => format!("{} bytes, {} ms", response.bytes, format_fping_ms(rtt)),
+        _ => "timed out".to_string(),
+    };
+    let avg = stats.avg_rtt().map_or("NaN".to_string(), format_fping_ms);
+    let loss = ((stats.sent - stats.received) * 100)
+        .checked_div(stats.sent)
+        .unwrap_or(0);
+    println!(
+        "{:<width$} : [{}], {} ({} avg, {}% loss)",
+        name,
+        index,
+        result,
+        avg,
+        loss,
+        width = width
+    );
+}
+
+/// fping -C结束时每个目标一行的报告，丢失的探测为`-`
+pub fn format_fping_report(name: &str, width: usize, samples: &[Option<Duration>]) -> String {
+    let mut line = format!("{:<width$} :", name, width = width);
+    for sample in samples {
+        match sample {
+            Some(rtt) => line.push_str(&format!(" {}", format_fping_ms(*rtt))),
+            None => line.push_str(" -"),
+        }
+    }
+    line
+}
+
+pub fn print_trace_start(host: &str, addr: &str, max_hops: u32, size: usize) {
+    println!(
+        "traceroute to {} ({}), {} hops max, {} byte packets",
+        host, addr, max_hops, size
+    );
+}
+
+pub fn print_trace_hop(hop: &Hop) {
+    if hop.addrs.is_empty() {
+        println!("{:>2}  {}", hop.ttl, "* * *".red());
+        return;
+    }
+
+    let mut line = format!("{:>2}  ", hop.ttl);
+    let first = hop.addrs[0];
+    match &hop.hostname {
+        Some(name) => line.push_str(&format!("{} ({})", name, first)),
+        None => line.push_str(&first.to_string()),
+    }
+    for addr in &hop.addrs[1..] {
+        line.push_str(&format!(", {}", addr));
+    }
+
+    if !hop.rtts.is_empty() {
+        line.push_str(&format!(
+            "  {}/{}/{}",
+            format_duration(hop.min_rtt().unwrap()),
+            format_duration(hop.avg_rtt().unwrap()),
+            format_duration(hop.max_rtt().unwrap())
+        ));
+    }
+
+    let lost = hop.sent as usize - hop.rtts.len();
+    if lost > 0 {
+        line.push_str(&format!("  {}", "*".repeat(lost).red()));
+    }
+
+    if let Some(error) = &hop.error {
+        line.push_str(&format!("  !{}", error.red()));
+    }
+
+    println!("{}", line);
+}
+
+/// 毫秒值，保留3位小数
+fn duration_ms(duration: Option<Duration>) -> Option<f64> {
+    duration.map(|d| (d.as_secs_f64() * 1_000_000.0).round() / 1000.0)
+}
+
+pub fn print_trace_json(trace: &TraceResult) -> String {
+    let json_data = serde_json::json!({
+        "host": trace.target.name,
+        "addr": trace.target.addr.to_string(),
+        "max_hops": trace.max_hops,
+        "packet_size": trace.size,
+        "reached": trace.reached,
+        "hops": trace.hops.iter().map(|hop| {
+            serde_json::json!({
+                "hop": hop.ttl,
+                "addrs": hop.addrs.iter().map(|addr| addr.to_string()).collect::<Vec<_>>(),
+                "hostname": hop.hostname,
+                "probes_sent": hop.sent,
+                "probes_received": hop.rtts.len(),
+                "rtt_ms": {
+                    "min": duration_ms(hop.min_rtt()),
+                    "avg": duration_ms(hop.avg_rtt()),
+                    "max": duration_ms(hop.max_rtt()),
+                },
+                "error": hop.error,
+            })
+        }).collect::<Vec<_>>()
+    });
+
+    serde_json::to_string_pretty(&json_data).unwrap_or_default()
+}
+
+pub fn print_trace_csv(trace: &TraceResult) -> String {
+    let mut lines = vec![
+        "hop,address,hostname,probes_sent,probes_received,rtt_min_ms,rtt_avg_ms,rtt_max_ms,error"
+            .to_string(),
+    ];
+
+    for hop in &trace.hops {
+        let ms =
+            |d: Option<Duration>| duration_ms(d).map_or(String::new(), |v| format!("{:.3}", v));
+        lines.push(format!(
+            "{},{},{},{},{},{},{},{},{}",
+            hop.ttl,
+            hop.addrs
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            hop.hostname.clone().unwrap_or_default(),
+            hop.sent,
+            hop.rtts.len(),
+            ms(hop.min_rtt()),
+            ms(hop.avg_rtt()),
+            ms(hop.max_rtt()),
+            hop.error.clone().unwrap_or_default()
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// mtr报告和实时界面共用的表头，与`mtr_hop_row`的列对齐
+pub(crate) const MTR_HEADER: &str =
+    "Hop  Host                                      Loss%   Snt    Last     Avg    Best    Wrst   StDev";
+
+pub(crate) fn mtr_hop_host(hop: &MtrHop) -> String {
+    match (&hop.hostname, hop.addrs.first()) {
+        (Some(name), Some(addr)) => format!("{} ({})", name, addr),
+        (None, Some(addr)) => addr.to_string(),
+        _ => "???".to_string(),
+    }
+}
+
+pub fn print_mtr_report(report: &MtrReport) {
+    println!(
+        "HOST: {} ({}), {} cycles, {} byte packets",
+        report.target.name, report.target.addr, report.cycles, report.size
+    );
+    println!("{}", MTR_HEADER);
+
+    for hop in &report.hops {
+        let loss = hop.stats.loss_percent();
+        let line = mtr_hop_row(hop, &mtr_hop_host(hop));
+        if hop.stats.sent > 0 && loss >= 100.0 {
+            println!("{}", line.red());
+        } else if loss > 0.0 {
+            println!("{}", line.yellow());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// 一跳的统计行，主机列由调用方给出，实时界面会按列宽截断
+pub(crate) fn mtr_hop_row(hop: &MtrHop, host: &str) -> String {
+    let ms = |d: Option<Duration>| duration_ms(d).map_or("-".to_string(), |v| format!("{:.1}", v));
+    format!(
+        "{:>3}. {:<40} {:>5.1}% {:>5} {:>7} {:>7} {:>7} {:>7} {:>7}",
+        hop.ttl,
+        host,
+        hop.stats.loss_percent(),
+        hop.stats.sent,
+        ms(hop.stats.last_rtt),
+        ms(hop.stats.avg_rtt()),
+        ms(hop.stats.min_rtt),
+        ms(hop.stats.max_rtt),
+        ms(hop.stats.stddev_rtt())
+    )
+}
+
+pub fn print_mtr_json(report: &MtrReport) -> String {
+    let json_data = serde_json::json!({
+        "host": report.target.name,
+        "addr": report.target.addr.to_string(),
+        "packet_size": report.size,
+        "cycles": report.cycles,
+        "hops": report.hops.iter().map(|hop| {
+            serde_json::json!({
+                "hop": hop.ttl,
+                "addrs": hop.addrs.iter().map(|addr| addr.to_string()).collect::<Vec<_>>(),
+                "hostname": hop.hostname,
+                "sent": hop.stats.sent,
+                "received": hop.stats.received,
+                "loss_percent": (hop.stats.loss_percent() * 100.0).round() / 100.0,
+                "rtt_ms": {
+                    "last": duration_ms(hop.stats.last_rtt),
+                    "avg": duration_ms(hop.stats.avg_rtt()),
+                    "best": duration_ms(hop.stats.min_rtt),
+                    "worst": duration_ms(hop.stats.max_rtt),
+                    "stddev": duration_ms(hop.stats.stddev_rtt()),
+                },
+            })
+        }).collect::<Vec<_>>()
+    });
+
+    serde_json::to_string_pretty(&json_data).unwrap_or_default()
+}
+
+pub fn print_mtr_csv(report: &MtrReport) -> String {
+    let mut lines = vec![
+        "hop,address,hostname,sent,received,loss_percent,last_ms,avg_ms,best_ms,worst_ms,stddev_ms"
+            .to_string(),
+    ];
+
+    for hop in &report.hops {
+        let ms =
+            |d: Option<Duration>| duration_ms(d).map_or(String::new(), |v| format!("{:.3}", v));
+        lines.push(format!(
+            "{},{},{},{},{},{:.2},{},{},{},{},{}",
+            hop.ttl,
+            hop.addrs
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            hop.hostname.clone().unwrap_or_default(),
+            hop.stats.sent,
+            hop.stats.received,
+            hop.stats.loss_percent(),
+            ms(hop.stats.last_rtt),
+            ms(hop.stats.avg_rtt()),
+            ms(hop.stats.min_rtt),
+            ms(hop.stats.max_rtt),
+            ms(hop.stats.stddev_rtt())
+        ));
+    }
+
+    lines.join("\n")
+}
+
+pub fn print_pmtu_start(host: &str, addr: &str, min_size: usize, max_mtu: usize) {
+    println!(
+        "PMTU {} ({}): searching {} to {} bytes (MTU), DF set",
+        host, addr, min_size, max_mtu
+    );
+}
+
+pub fn print_pmtu_step(step: &PmtuStep) {
+    let status = match &step.outcome {
+        ProbeOutcome::Fits => format!(
+            "{} time={}",
+            "ok".green(),
+            step.rtt.map_or("-".to_string(), format_duration)
+        ),
+        ProbeOutcome::TooBig { router, mtu } => format!(
+            "{} from {} (mtu = {})",
+            "Frag needed and DF set".yellow(),
+            router,
+            mtu
+        ),
+        ProbeOutcome::LocalTooBig => format!("{}", "Message too long (local)".yellow()),
+        ProbeOutcome::Timeout => format!("{}", "timeout".red()),
+    };
+    println!("{:>6} bytes: {}", step.size, status);
+}
+
+pub fn print_pmtu_result(result: &PmtuResult) {
+    println!(
+        "\n--- {} path MTU ---\npath MTU {} bytes, largest ICMP packet {} bytes ({} data bytes)",
+        result.target.name,
+        result.path_mtu(),
+        result.largest_size,
+        result.largest_payload()
+    );
+}
+
+pub fn print_pmtu_json(result: &PmtuResult) -> String {
+    let json_data = serde_json::json!({
+        "host": result.target.name,
+        "addr": result.target.addr.to_string(),
+        "path_mtu": result.path_mtu(),
+        "largest_packet_size": result.largest_size,
+        "largest_payload_size": result.largest_payload(),
+        "probes": result.steps.iter().map(|step| {
+            let (router, mtu) = match &step.outcome {
+                ProbeOutcome::TooBig { router, mtu } => (Some(router.to_string()), Some(*mtu)),
+                _ => (None, None),
+            };
+            serde_json::json!({
+                "size": step.size,
+                "result": step.outcome.name(),
+                "rtt_ms": duration_ms(step.rtt),
+                "router": router,
+                "mtu": mtu,
+            })
+        }).collect::<Vec<_>>()
+    });
+
+    serde_json::to_string_pretty(&json_data).unwrap_or_default()
+}
+
+pub fn print_pmtu_csv(result: &PmtuResult) -> String {
     format!(
-        "host,packets_transmitted,packets_received,packet_loss_percent,rtt_min_ms,rtt_avg_ms,rtt_max_ms\n{},{},{},{:.1},{:.3},{:.3},{:.3}",
-        host, stats.sent, stats.received, stats.loss_percent(),
-        min, avg, max
+        "host,addr,path_mtu,largest_packet_size,largest_payload_size,probes\n{},{},{},{},{},{}",
+        result.target.name,
+        result.target.addr,
+        result.path_mtu(),
+        result.largest_size,
+        result.largest_payload(),
+        result.steps.len()
     )
 }
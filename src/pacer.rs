@@ -0,0 +1,90 @@
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::time;
+
+/// 全局的发包调度器，所有探测在发送前从这里领取发送时刻
+///
+/// 每个目标的任务各自按`period`休眠，大量目标会在同一时刻一起发出；
+/// 经过调度器后任意两个包之间至少相隔`interval`，大规模扫描会被均匀摊开
+#[derive(Debug)]
+pub struct Pacer {
+    interval: Duration,
+    /// 下一个可用的发送时刻
+    next: Mutex<Instant>,
+}
+
+/// 进程内共享的调度器，未配置时不限速
+static PACER: OnceLock<Pacer> = OnceLock::new();
+
+impl Pacer {
+    /// 按每秒包数和最小发包间隔创建调度器，取两者中较严格的一个；都未指定时返回None
+    pub fn new(rate: Option<u32>, min_interval: Option<Duration>) -> Option<Self> {
+        let rate_interval = rate
+            .filter(|rate| *rate > 0)
+            .map(|rate| Duration::from_secs(1) / rate);
+        let interval = match (rate_interval, min_interval) {
+            (Some(a), Some(b)) => a.max(b),
+            (a, b) => a.or(b)?,
+        };
+
+        Some(Self {
+            interval,
+            next: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// 领取下一个发送时刻：不早于现在，且与上一个领取的时刻相隔`interval`
+    fn reserve(&self, now: Instant) -> Instant {
+        let mut next = self.next.lock().unwrap();
+        let slot = (*next).max(now);
+        *next = slot + self.interval;
+        slot
+    }
+}
+
+/// 配置全局调度器，只有第一次调用生效；`rate`为每秒包数，`min_interval_ms`为任意两个包的最小间隔
+pub fn configure(rate: Option<u32>, min_interval_ms: Option<u64>) {
+    if let Some(pacer) = Pacer::new(rate, min_interval_ms.map(Duration::from_millis)) {
+        let _ = PACER.set(pacer);
+    }
+}
+
+/// 等待轮到本次发送，未配置调度器时立即返回
+pub async fn wait() {
+    if let Some(pacer) = PACER.get() {
+        let slot = pacer.reserve(Instant::now());
+        time::sleep_until(slot.into()).await;
+    }
+}
+
+/// `wait`的阻塞版本，用于在`spawn_blocking`中发送的ARP和邻居发现
+pub fn wait_blocking() {
+    if let Some(pacer) = PACER.get() {
+        let now = Instant::now();
+        let slot = pacer.reserve(now);
+        std::thread::sleep(slot.saturating_duration_since(now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots_are_spaced_by_the_stricter_limit() {
+        assert!(Pacer::new(None, None).is_none());
+
+        // 100 pps即10ms，比5ms的最小间隔更严格
+        let pacer = Pacer::new(Some(100), Some(Duration::from_millis(5))).unwrap();
+
+        let start = Instant::now();
+        let slots: Vec<Instant> = (0..3).map(|_| pacer.reserve(start)).collect();
+        assert_eq!(slots[0], start);
+        assert_eq!(slots[1] - slots[0], Duration::from_millis(10));
+        assert_eq!(slots[2] - slots[1], Duration::from_millis(10));
+
+        // 空闲之后不会补发积攒的额度
+        let later = start + Duration::from_secs(1);
+        assert_eq!(pacer.reserve(later), later);
+    }
+}
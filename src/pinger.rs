@@ -1,94 +1,127 @@
-use pnet::packet::ip::IpNextHeaderProtocols;
-use pnet::packet::ipv4::Ipv4Packet;
-use pnet::packet::Packet;
 use rand::random;
-use socket2::{Domain, Protocol, Socket, Type};
-use std::mem::MaybeUninit;
-use std::net::{IpAddr, SocketAddr};
-use std::time::{Duration, Instant};
+use std::future::Future;
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::mpsc;
 use tokio::time;
 
-// SOCK_RAW constant for raw sockets
-#[cfg(unix)]
-const SOCK_RAW: i32 = 3;
-#[cfg(windows)]
-const SOCK_RAW: i32 = 3;
-
+use crate::dispatcher::{ProbeKey, ReplyDispatcher};
 use crate::error::PingError;
-use crate::host::{PingResponse, PingTarget};
-use crate::icmp::{parse_echo_reply, IcmpEchoRequest};
+use crate::host::{PingResponse, PingTarget, Probe};
+use crate::icmp::{
+    create_timestamp_request, ms_since_midnight, verify_payload, IcmpEchoRequest, IcmpReply,
+    TIMESTAMP_PACKET_LEN,
+};
+use crate::pacer;
+use crate::rto::RtoEstimator;
+use crate::socket::{Binding, Family, SocketKind, SocketOptions};
+use crate::tracker::{ReplyTracker, SharedTracker};
 
 /// Pinger结构体，用于发送和接收ICMP包
 pub struct Pinger {
     /// ICMP包的标识符
-    #[allow(dead_code)]
     identifier: u16,
-    /// socket对象，用于发送和接收ICMP包
-    socket: Socket,
+    /// 共享的回复分发器，用于发送ICMP包并接收属于本Pinger的回复
+    dispatcher: Arc<ReplyDispatcher>,
     /// 目标主机的信息
     target: PingTarget,
+    /// 本机发往目标时使用的源地址，用于计算ICMPv6伪首部校验和，并在响应中报告
+    source: Option<IpAddr>,
     /// ICMP包的大小
     size: usize,
     /// TTL值
     ttl: u32,
+    /// 数据部分的填充模式
+    pattern: Option<Vec<u8>>,
+    /// 发送ICMP时间戳请求而不是回显请求
+    timestamp: bool,
+    /// 记录每个探测的状态，识别迟到、重复和乱序的回复
+    tracker: SharedTracker,
 }
 
 impl Pinger {
     /// 创建一个新的Pinger对象
     ///
+    /// 同一地址族、相同TTL和绑定的Pinger共用一个socket，不会为每个目标单独创建socket
+    ///
     /// # 参数
     ///
     /// * `target`: 目标主机的信息
     /// * `size`: ICMP包的大小
     /// * `ttl`: TTL值
+    /// * `binding`: 绑定的源地址和网卡
     ///
     /// # 返回值
     ///
     /// * `Result<Self, PingError>`: 如果创建成功，返回Pinger对象；如果创建失败，返回错误信息
-    pub fn new(target: PingTarget, size: usize, ttl: u32) -> Result<Self, PingError> {
+    pub fn new(
+        target: PingTarget,
+        size: usize,
+        ttl: u32,
+        binding: &Binding,
+    ) -> Result<Self, PingError> {
+        let options = SocketOptions {
+            family: Family::of(&target.addr),
+            ttl,
+            dont_fragment: false,
+            binding: binding.clone(),
+            dscp: 0,
+        };
+        Self::with_socket_options(target, size, options)
+    }
+
+    /// 使用指定的socket配置创建Pinger，例如设置DF位或绑定源地址和网卡
+    ///
+    /// # 参数
+    ///
+    /// * `target`: 目标主机的信息
+    /// * `size`: ICMP包的大小
+    /// * `options`: socket配置，地址族必须与目标地址一致
+    pub fn with_socket_options(
+        target: PingTarget,
+        size: usize,
+        options: SocketOptions,
+    ) -> Result<Self, PingError> {
         let identifier = random::<u16>();
+        let ttl = options.ttl;
 
-        let socket = match target.addr {
-            IpAddr::V4(_) => {
-                let socket =
-                    Socket::new(Domain::IPV4, Type::from(SOCK_RAW), Some(Protocol::ICMPV4))
-                        .map_err(|e| {
-                            if e.kind() == std::io::ErrorKind::PermissionDenied {
-                                PingError::PermissionDenied
-                            } else {
-                                PingError::SendError(e)
-                            }
-                        })?;
-                socket.set_ttl(ttl)?;
-                socket
-            }
-            IpAddr::V6(_) => {
-                let socket =
-                    Socket::new(Domain::IPV6, Type::from(SOCK_RAW), Some(Protocol::ICMPV6))
-                        .map_err(|e| {
-                            if e.kind() == std::io::ErrorKind::PermissionDenied {
-                                PingError::PermissionDenied
-                            } else {
-                                PingError::SendError(e)
-                            }
-                        })?;
-                socket.set_unicast_hops_v6(ttl)?;
-                socket
-            }
-        };
+        let source = options.binding.source_for(target.addr);
+        let dispatcher = ReplyDispatcher::shared(options)?;
 
-        socket.set_nonblocking(true)?;
+        let tracker = Arc::new(Mutex::new(ReplyTracker::new(target.clone())));
+        dispatcher.track(identifier, target.addr, tracker.clone())?;
 
         Ok(Self {
             identifier,
-            socket,
+            dispatcher,
+            source,
             target,
             size,
             ttl,
+            pattern: None,
+            timestamp: false,
+            tracker,
         })
     }
 
+    /// 设置数据部分的填充模式（类似ping -p）
+    pub fn with_pattern(mut self, pattern: Option<Vec<u8>>) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    /// 发送ICMP时间戳请求（类型13）代替回显请求，用于估算对端的时钟偏差
+    pub fn with_timestamp_requests(mut self, timestamp: bool) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// 是否能发送时间戳请求：只有IPv4有时间戳报文，且数据报套接字只允许回显请求
+    pub fn supports_timestamp(&self) -> bool {
+        self.target.addr.is_ipv4() && self.dispatcher.socket_kind() == SocketKind::Raw
+    }
+
     /// 发送一个ICMP包并等待响应
     ///
     /// # 参数
@@ -100,106 +133,159 @@ impl Pinger {
     ///
     /// * `PingResponse`: ICMP包的响应信息
     pub async fn ping_once(&self, seq: u16, timeout_ms: u64) -> PingResponse {
+        pacer::wait().await;
+        let mut response = if self.timestamp {
+            self.timestamp_once(seq, timeout_ms).await
+        } else {
+            self.echo_once(seq, timeout_ms).await
+        };
+        response.source = self.source;
+        response
+    }
+
+    /// 发送一个ICMP回显请求并等待回显应答
+    async fn echo_once(&self, seq: u16, timeout_ms: u64) -> PingResponse {
         let mut buffer = vec![0; self.size];
-        let request = IcmpEchoRequest::new(self.identifier, seq, self.size);
-
-        match request.create_packet(&mut buffer) {
-            Ok(packet) => {
-                let socket_addr = SocketAddr::new(self.target.addr, 0);
-                let start = Instant::now();
-
-                match self.socket.send_to(packet.packet(), &socket_addr.into()) {
-                    Ok(_) => {
-                        // Create a buffer for receiving with MaybeUninit
-                        let mut recv_buffer = [MaybeUninit::new(0u8); 2048];
-
-                        // Wait for response with timeout
-                        let timeout_duration = Duration::from_millis(timeout_ms);
-                        let timeout_instant = start + timeout_duration;
-
-                        loop {
-                            let now = Instant::now();
-                            if now >= timeout_instant {
-                                return PingResponse::failure(
-                                    self.target.clone(),
-                                    seq,
-                                    self.size,
-                                    self.ttl as u8,
-                                    PingError::Timeout,
-                                );
-                            }
-
-                            // Use socket2's recv with MaybeUninit buffer
-                            match self.socket.recv(&mut recv_buffer) {
-                                Ok(len) => {
-                                    // Convert MaybeUninit buffer to initialized buffer for processing
-                                    let recv_data = unsafe {
-                                        std::slice::from_raw_parts(
-                                            recv_buffer.as_ptr() as *const u8,
-                                            len,
-                                        )
-                                    };
-
-                                    // Parse the received packet
-                                    if len >= Ipv4Packet::minimum_packet_size() {
-                                        if let Some(ipv4_packet) = Ipv4Packet::new(recv_data) {
-                                            if ipv4_packet.get_next_level_protocol()
-                                                == IpNextHeaderProtocols::Icmp
-                                            {
-                                                let icmp_packet_offset =
-                                                    (ipv4_packet.get_header_length() * 4) as usize;
-
-                                                if let Some(reply) = parse_echo_reply(
-                                                    recv_data,
-                                                    icmp_packet_offset,
-                                                    self.identifier,
-                                                    seq,
-                                                    start,
-                                                    ipv4_packet.get_ttl(),
-                                                ) {
-                                                    return PingResponse::success(
-                                                        self.target.clone(),
-                                                        seq,
-                                                        reply.rtt,
-                                                        reply.size,
-                                                        reply.ttl,
-                                                    );
-                                                }
-                                            }
-                                        }
-                                    }
-
-                                    // Continue waiting if this wasn't our packet
-                                    time::sleep(Duration::from_millis(1)).await;
-                                }
-                                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                                    // No data available yet, wait a bit and try again
-                                    time::sleep(Duration::from_millis(1)).await;
-                                }
-                                Err(e) => {
-                                    return PingResponse::failure(
-                                        self.target.clone(),
-                                        seq,
-                                        self.size,
-                                        self.ttl as u8,
-                                        PingError::SendError(e),
-                                    );
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => PingResponse::failure(
-                        self.target.clone(),
-                        seq,
-                        self.size,
-                        self.ttl as u8,
-                        PingError::SendError(e),
-                    ),
-                }
+        let mut request = IcmpEchoRequest::new(self.identifier, seq, self.size);
+        if let Some(pattern) = &self.pattern {
+            request = request.with_pattern(pattern.clone());
+        }
+
+        let built = match self.target.addr {
+            IpAddr::V4(_) => request.create_packet(&mut buffer).map(|_| ()),
+            IpAddr::V6(destination) => {
+                let source = match self.source {
+                    Some(IpAddr::V6(source)) => source,
+                    _ => Ipv6Addr::UNSPECIFIED,
+                };
+                request
+                    .create_packet_v6(&mut buffer, source, destination)
+                    .map(|_| ())
+            }
+        };
+
+        if let Err(e) = built {
+            // 这里的e是PingError类型，直接传递
+            return PingResponse::failure(self.target.clone(), seq, self.size, self.ttl as u8, e);
+        }
+
+        // 先登记再发送，避免回复在登记之前到达
+        let key = ProbeKey::new(self.identifier, seq, self.target.addr);
+        let reply_rx = self.dispatcher.register(key);
+        let start = Instant::now();
+        self.tracker.lock().unwrap().sent(seq, start);
+
+        if let Err(e) = self.dispatcher.send_to(&buffer, self.target.addr).await {
+            self.dispatcher.cancel(&key);
+            return PingResponse::failure(
+                self.target.clone(),
+                seq,
+                self.size,
+                self.ttl as u8,
+                PingError::SendError(e),
+            );
+        }
+
+        // 回复由分发器的接收任务送达，这里只需等待通道或超时
+        match time::timeout(Duration::from_millis(timeout_ms), reply_rx).await {
+            Ok(Ok(IcmpReply::Echo(reply))) => {
+                let mut response = PingResponse::success(
+                    self.target.clone(),
+                    seq,
+                    reply.received_at.saturating_duration_since(start),
+                    reply.size,
+                    reply.ttl,
+                );
+                // 回显的数据应与发出的完全一致，网卡或链路可能截断或改写数据
+                response.payload_check = verify_payload(&buffer[8..], &reply.payload);
+                response.class = self.tracker.lock().unwrap().answered(seq);
+                response.timestamp_source = reply.timestamp_source;
+                response
+            }
+            // 路由器返回了ICMP差错报文，无需等到超时
+            Ok(Ok(IcmpReply::Error(error))) => PingResponse::icmp_error(
+                self.target.clone(),
+                seq,
+                error.received_at.saturating_duration_since(start),
+                self.size,
+                self.ttl as u8,
+                error.to_error(),
+            ),
+            _ => {
+                self.dispatcher.cancel(&key);
+                self.tracker.lock().unwrap().expired(seq);
+                PingResponse::failure(
+                    self.target.clone(),
+                    seq,
+                    self.size,
+                    self.ttl as u8,
+                    PingError::Timeout,
+                )
+            }
+        }
+    }
+
+    /// 发送一个ICMP时间戳请求并等待时间戳应答
+    async fn timestamp_once(&self, seq: u16, timeout_ms: u64) -> PingResponse {
+        let mut buffer = [0u8; TIMESTAMP_PACKET_LEN];
+        let failure = |error| {
+            let mut response = PingResponse::failure(
+                self.target.clone(),
+                seq,
+                TIMESTAMP_PACKET_LEN,
+                self.ttl as u8,
+                error,
+            );
+            response.probe = Probe::Timestamp { clock: None };
+            response
+        };
+        if let Err(e) = create_timestamp_request(&mut buffer, self.identifier, seq) {
+            return failure(e);
+        }
+
+        let key = ProbeKey::new(self.identifier, seq, self.target.addr);
+        let reply_rx = self.dispatcher.register(key);
+        let start = Instant::now();
+        self.tracker.lock().unwrap().sent(seq, start);
+
+        if let Err(e) = self.dispatcher.send_to(&buffer, self.target.addr).await {
+            self.dispatcher.cancel(&key);
+            return failure(PingError::SendError(e));
+        }
+
+        match time::timeout(Duration::from_millis(timeout_ms), reply_rx).await {
+            Ok(Ok(IcmpReply::Timestamp(reply))) => {
+                let mut response = PingResponse::success(
+                    self.target.clone(),
+                    seq,
+                    reply.received_at.saturating_duration_since(start),
+                    TIMESTAMP_PACKET_LEN,
+                    reply.ttl,
+                );
+                response.class = self.tracker.lock().unwrap().answered(seq);
+                // 把到达时刻换算为墙上时钟，与对端的时间戳比较
+                let arrival = ms_since_midnight(SystemTime::now() - reply.received_at.elapsed());
+                response.probe = Probe::Timestamp {
+                    clock: reply.estimate(arrival),
+                };
+                response
             }
-            Err(e) => {
-                // 这里的e是PingError类型，直接传递
-                PingResponse::failure(self.target.clone(), seq, self.size, self.ttl as u8, e)
+            Ok(Ok(IcmpReply::Error(error))) => {
+                let mut response = PingResponse::icmp_error(
+                    self.target.clone(),
+                    seq,
+                    error.received_at.saturating_duration_since(start),
+                    TIMESTAMP_PACKET_LEN,
+                    self.ttl as u8,
+                    error.to_error(),
+                );
+                response.probe = Probe::Timestamp { clock: None };
+                response
+            }
+            _ => {
+                self.dispatcher.cancel(&key);
+                self.tracker.lock().unwrap().expired(seq);
+                failure(PingError::Timeout)
             }
         }
     }
@@ -208,10 +294,10 @@ impl Pinger {
     ///
     /// # 参数
     ///
-    /// * `count`: 发送的ICMP包数量
+    /// * `count`: 发送的ICMP包数量，为0时一直发送直到接收方关闭
     /// * `period_ms`: 发送ICMP包之间的间隔时间（毫秒）
-    /// * `timeout_ms`: 等待响应的超时时间（毫秒）
-    /// * `retry`: 如果发送失败，重试的次数
+    /// * `timeout_ms`: 等待响应的超时时间（毫秒），启用自适应超时时为上限
+    /// * `retry`: 失败后的重试次数和退避策略
     /// * `tx`: 用于发送响应信息的通道
     ///
     /// # 返回值
@@ -222,46 +308,247 @@ impl Pinger {
         count: u32,
         period_ms: u64,
         timeout_ms: u64,
-        retry: u32,
+        retry: RetryPolicy,
         tx: mpsc::Sender<PingResponse>,
     ) -> Result<(), PingError> {
-        let mut seq_num = 0;
+        // 迟到和重复的回复由分发器经跟踪器送入同一个通道
+        self.tracker.lock().unwrap().set_sink(Some(tx.clone()));
 
-        for _ in 0..count {
-            let mut retry_count = 0;
-            let mut success = false;
+        let result = run_probes(
+            count,
+            period_ms,
+            timeout_ms,
+            retry,
+            tx,
+            |seq, timeout_ms| self.ping_once(seq, timeout_ms),
+        )
+        .await;
 
-            while retry_count <= retry && !success {
-                let response = self.ping_once(seq_num, timeout_ms).await;
+        // 释放通道的发送端，否则接收方无法结束
+        self.tracker.lock().unwrap().set_sink(None);
 
-                if response.is_success() {
-                    success = true;
-                } else {
-                    retry_count += 1;
-                }
+        result
+    }
+}
+
+/// 第一次重试之前的等待时间（毫秒）
+const RETRY_DELAY_MS: f64 = 100.0;
 
-                match tx.send(response).await {
-                    Ok(_) => {}
-                    Err(_) => {
-                        // 接收方已关闭，我们可以安全地退出
-                        return Ok(());
+/// 探测失败后的重试策略
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// 重试次数
+    pub retry: u32,
+    /// 退避因子（同fping -B）：每次重试的超时和重试前的等待时间都乘以该因子
+    pub backoff: f64,
+    /// 按RFC 6298根据每个目标的RTT估计超时，而不是每次都使用固定的超时；
+    /// 此时超时按RFC 6298加倍，不再乘以退避因子
+    pub adaptive_timeout: bool,
+}
+
+impl RetryPolicy {
+    /// 固定超时、不退避的重试策略
+    pub fn new(retry: u32) -> Self {
+        Self {
+            retry,
+            backoff: 1.0,
+            adaptive_timeout: false,
+        }
+    }
+
+    /// 实际使用的退避因子，自适应超时自带退避，不再叠加
+    fn factor(&self) -> f64 {
+        if self.adaptive_timeout {
+            1.0
+        } else {
+            self.backoff
+        }
+    }
+
+    /// 固定超时时第`attempt`次尝试使用的超时，0为第一次发送
+    fn timeout_ms(&self, base_ms: u64, attempt: u32) -> u64 {
+        (base_ms as f64 * self.factor().powi(attempt as i32)).round() as u64
+    }
+
+    /// 第`attempt`次重试（从1开始）之前的等待时间
+    fn delay(&self, attempt: u32) -> Duration {
+        Duration::from_secs_f64(RETRY_DELAY_MS * self.factor().powi(attempt as i32 - 1) / 1000.0)
+    }
+}
+
+/// 按次数、间隔和重试策略反复调用`probe`，把每个响应送入通道
+///
+/// ICMP和TCP等各种探测方式共用这一循环，`probe`的参数为序号和超时时间（毫秒）；
+/// `count`为0时一直探测，序号超过65535后回绕
+pub async fn run_probes<F, Fut>(
+    count: u32,
+    period_ms: u64,
+    timeout_ms: u64,
+    policy: RetryPolicy,
+    tx: mpsc::Sender<PingResponse>,
+    probe: F,
+) -> Result<(), PingError>
+where
+    F: Fn(u16, u64) -> Fut,
+    Fut: Future<Output = PingResponse>,
+{
+    let mut seq_num: u16 = 0;
+    let mut round: u64 = 0;
+    let mut rto = policy
+        .adaptive_timeout
+        .then(|| RtoEstimator::new(timeout_ms));
+
+    'probes: loop {
+        let mut retry_count = 0;
+        let mut success = false;
+
+        while retry_count <= policy.retry && !success {
+            // 自适应超时在每次超时后加倍，且不超过--timeout
+            let attempt_timeout_ms = match &rto {
+                Some(rto) => rto.timeout_ms(),
+                None => policy.timeout_ms(timeout_ms, retry_count),
+            };
+            let mut response = probe(seq_num, attempt_timeout_ms).await;
+            // 重试使用新的序号，避免前一次的迟到回复被当作重试的回复
+            seq_num = seq_num.wrapping_add(1);
+            response.probe_seq = Some(round);
+            response.attempt = retry_count;
+
+            if response.is_success() {
+                success = true;
+            } else {
+                retry_count += 1;
+            }
+            response.final_attempt = success || retry_count > policy.retry;
+
+            if let Some(rto) = &mut rto {
+                if success {
+                    if let Some(rtt) = response.rtt {
+                        rto.sample(rtt);
                     }
+                } else if matches!(response.error, Some(PingError::Timeout)) {
+                    rto.timed_out();
                 }
+            }
 
-                if !success && retry_count <= retry {
-                    // Wait a short time before retrying
-                    time::sleep(Duration::from_millis(100)).await;
+            match tx.send(response).await {
+                Ok(_) => {}
+                Err(_) => {
+                    // 接收方已关闭，我们可以安全地退出
+                    break 'probes;
                 }
             }
 
-            seq_num += 1;
+            if !success && retry_count <= policy.retry {
+                // Wait a short time before retrying
+                time::sleep(policy.delay(retry_count)).await;
+            }
+        }
 
-            // Wait for the specified period before sending the next ping
-            if seq_num < count as u16 {
-                time::sleep(Duration::from_millis(period_ms)).await;
+        round += 1;
+        if count != 0 && round >= count as u64 {
+            break;
+        }
+
+        // Wait for the specified period before sending the next ping
+        if period_ms > 0 {
+            time::sleep(Duration::from_millis(period_ms)).await;
+        }
+    }
+
+    Ok(())
+}
+
+impl Drop for Pinger {
+    fn drop(&mut self) {
+        self.dispatcher
+            .untrack(self.identifier, self.target.addr, &self.tracker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sequence_wraps_past_u16_max() {
+        let count = u16::MAX as u32 + 3;
+        let (tx, mut rx) = mpsc::channel::<PingResponse>(16);
+        let target = PingTarget::new("localhost".to_string(), "127.0.0.1".parse().unwrap());
+
+        let collector = tokio::spawn(async move {
+            let mut seqs = Vec::new();
+            while let Some(response) = rx.recv().await {
+                seqs.push(response.seq);
             }
+            seqs
+        });
+        run_probes(count, 0, 0, RetryPolicy::new(0), tx, |seq, _| {
+            let target = target.clone();
+            async move { PingResponse::success(target, seq, Duration::ZERO, 0, 64) }
+        })
+        .await
+        .unwrap();
+
+        let seqs = collector.await.unwrap();
+        assert_eq!(seqs.len(), count as usize);
+        assert_eq!(seqs[seqs.len() - 4..], [u16::MAX - 1, u16::MAX, 0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_retries_back_off_and_adapt_timeout() {
+        let (tx, mut rx) = mpsc::channel::<PingResponse>(16);
+        let target = PingTarget::new("localhost".to_string(), "127.0.0.1".parse().unwrap());
+        let timeouts = Mutex::new(Vec::new());
+        let policy = RetryPolicy {
+            backoff: 2.0,
+            adaptive_timeout: true,
+            ..RetryPolicy::new(2)
+        };
+
+        // 第一轮收到100ms的回复，第二轮全部超时，第三轮又收到回复
+        run_probes(3, 0, 1000, policy, tx, |seq, timeout_ms| {
+            timeouts.lock().unwrap().push(timeout_ms);
+            let target = target.clone();
+            async move {
+                if (1..=3).contains(&seq) {
+                    PingResponse::failure(target, seq, 0, 64, PingError::Timeout)
+                } else {
+                    PingResponse::success(target, seq, Duration::from_millis(100), 0, 64)
+                }
+            }
+        })
+        .await
+        .unwrap();
+        let mut attempts = Vec::new();
+        while let Ok(response) = rx.try_recv() {
+            attempts.push((
+                response.probe_seq.unwrap(),
+                response.attempt,
+                response.final_attempt,
+            ));
         }
+        assert_eq!(
+            attempts,
+            [
+                (0, 0, true),
+                (1, 0, false),
+                (1, 1, false),
+                (1, 2, true),
+                (2, 0, true)
+            ]
+        );
+
+        // 第一个回复之后按RTT计算为100+4*50；每次超时加倍但不超过--timeout，
+        // 退避因子在自适应模式下不再叠加
+        assert_eq!(*timeouts.lock().unwrap(), [1000, 300, 600, 1000, 1000]);
 
-        Ok(())
+        // 固定超时时重试的超时按因子递增
+        let fixed = RetryPolicy {
+            backoff: 2.0,
+            ..RetryPolicy::new(2)
+        };
+        assert_eq!(fixed.timeout_ms(1000, 2), 4000);
+        assert_eq!(fixed.delay(2), Duration::from_millis(200));
     }
 }
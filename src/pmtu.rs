@@ -0,0 +1,355 @@
+use std::future::Future;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::error::PingError;
+use crate::host::PingTarget;
+use crate::icmp::ICMP_HEADER_LEN;
+use crate::pinger::Pinger;
+use crate::socket::{Binding, Family, SocketOptions};
+
+/// IPv4头长度（不含选项）
+const IPV4_HEADER_LEN: usize = 20;
+/// IPv6固定头长度
+const IPV6_HEADER_LEN: usize = 40;
+
+/// 某个包大小的探测结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeOutcome {
+    /// 收到了Echo Reply
+    Fits,
+    /// 路由器返回了Fragmentation Needed / Packet Too Big
+    TooBig { router: IpAddr, mtu: u32 },
+    /// 超过本机已知的路径MTU（出接口MTU或缓存的PMTU），send返回EMSGSIZE
+    LocalTooBig,
+    /// 所有尝试都超时，可能是包被静默丢弃
+    Timeout,
+}
+
+impl ProbeOutcome {
+    /// 用于JSON/CSV输出的名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProbeOutcome::Fits => "ok",
+            ProbeOutcome::TooBig { .. } => "frag_needed",
+            ProbeOutcome::LocalTooBig => "local_too_big",
+            ProbeOutcome::Timeout => "timeout",
+        }
+    }
+}
+
+/// 一次探测
+#[derive(Debug, Clone)]
+pub struct PmtuStep {
+    /// ICMP包的大小（含8字节ICMP头）
+    pub size: usize,
+    pub outcome: ProbeOutcome,
+    pub rtt: Option<Duration>,
+}
+
+/// 路径MTU探测的结果
+#[derive(Debug, Clone)]
+pub struct PmtuResult {
+    pub target: PingTarget,
+    /// IP头长度，IPv4为20，IPv6为40
+    pub header_len: usize,
+    /// 能够到达目标的最大ICMP包大小
+    pub largest_size: usize,
+    pub steps: Vec<PmtuStep>,
+}
+
+impl PmtuResult {
+    /// 路径MTU，即最大能通过的IP包大小
+    pub fn path_mtu(&self) -> usize {
+        self.largest_size + self.header_len
+    }
+
+    /// 最大能通过的ICMP数据长度（不含ICMP头）
+    pub fn largest_payload(&self) -> usize {
+        self.largest_size - ICMP_HEADER_LEN
+    }
+}
+
+/// 设置DF位后二分查找能到达目标的最大包大小
+///
+/// 收到Fragmentation Needed / Packet Too Big时直接用其中的下一跳MTU收窄上界，
+/// 没有差错报文时以超时判断包被丢弃
+///
+/// # 参数
+///
+/// * `target`: 目标主机
+/// * `min_size`: 查找的下界（ICMP包大小），必须能到达目标
+/// * `max_mtu`: 查找的上界（IP包大小）
+/// * `tries`: 每个大小最多发送的探测数，全部超时才认为包过大
+/// * `timeout_ms`: 每个探测的超时时间（毫秒）
+/// * `ttl`: TTL值
+/// * `binding`: 绑定的源地址和网卡
+/// * `on_step`: 每完成一次探测时调用，用于实时输出
+#[allow(clippy::too_many_arguments)]
+pub async fn discover(
+    target: PingTarget,
+    min_size: usize,
+    max_mtu: usize,
+    tries: u32,
+    timeout_ms: u64,
+    ttl: u32,
+    binding: &Binding,
+    mut on_step: impl FnMut(&PmtuStep),
+) -> Result<PmtuResult, PingError> {
+    let family = Family::of(&target.addr);
+    let header_len = match family {
+        Family::V4 => IPV4_HEADER_LEN,
+        Family::V6 => IPV6_HEADER_LEN,
+    };
+    let options = SocketOptions {
+        family,
+        ttl,
+        dont_fragment: true,
+        binding: binding.clone(),
+        dscp: 0,
+    };
+
+    let min_size = min_size.max(ICMP_HEADER_LEN);
+    let max_size = max_mtu.saturating_sub(header_len);
+    if max_size < min_size {
+        return Err(PingError::Other(format!(
+            "max MTU {} is smaller than the minimum packet size {}",
+            max_mtu,
+            min_size + header_len
+        )));
+    }
+
+    // 每个大小最多发送tries个探测，为每个大小预留各自的序号
+    let mut next_seq = 0u16;
+    let (largest_size, steps) = search(
+        min_size,
+        max_size,
+        header_len,
+        |size| {
+            let first_seq = next_seq;
+            next_seq = next_seq.wrapping_add(tries.max(1) as u16);
+            probe(&target, &options, size, tries, timeout_ms, first_seq)
+        },
+        &mut on_step,
+    )
+    .await
+    .map_err(|e| match e {
+        SearchError::Unreachable => PingError::Other(format!(
+            "{} did not answer {}-byte packets",
+            target.addr, min_size
+        )),
+        SearchError::Probe(e) => e,
+    })?;
+
+    Ok(PmtuResult {
+        target,
+        header_len,
+        largest_size,
+        steps,
+    })
+}
+
+/// `search`失败的原因
+#[derive(Debug)]
+enum SearchError {
+    /// 最小的包也没有到达目标
+    Unreachable,
+    /// 探测本身出错，例如目标不可达
+    Probe(PingError),
+}
+
+/// 在`[min_size, max_size]`中二分查找能通过的最大包大小，返回该大小和全部探测
+///
+/// `probe`按给定的ICMP包大小探测一次；`header_len`用于把差错报文中的下一跳MTU
+/// 换算为ICMP包大小
+async fn search<F, Fut>(
+    min_size: usize,
+    max_size: usize,
+    header_len: usize,
+    mut probe: F,
+    on_step: &mut impl FnMut(&PmtuStep),
+) -> Result<(usize, Vec<PmtuStep>), SearchError>
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = Result<PmtuStep, PingError>>,
+{
+    let mut steps = Vec::new();
+
+    // 先确认最小的包能到达目标，否则无从判断
+    let step = probe(min_size).await.map_err(SearchError::Probe)?;
+    on_step(&step);
+    let reachable = step.outcome == ProbeOutcome::Fits;
+    steps.push(step);
+    if !reachable {
+        return Err(SearchError::Unreachable);
+    }
+
+    // 不变量：good能通过，bad不能通过（bad = max_size + 1表示上界尚未验证）
+    let mut good = min_size;
+    let mut bad = max_size + 1;
+    let mut hint = None;
+
+    while bad - good > 1 {
+        let size = match hint.take() {
+            Some(size) if size > good && size < bad => size,
+            _ => good + (bad - good) / 2,
+        };
+
+        let step = probe(size).await.map_err(SearchError::Probe)?;
+        on_step(&step);
+
+        match step.outcome {
+            ProbeOutcome::Fits => good = size,
+            ProbeOutcome::TooBig { mtu, .. } => {
+                bad = size;
+                // 大于下一跳MTU的包在这个路由器上一定会被拒绝，下一次直接尝试这个大小
+                let reported = (mtu as usize).saturating_sub(header_len);
+                if reported > good {
+                    bad = bad.min(reported + 1);
+                    hint = Some(reported);
+                }
+            }
+            ProbeOutcome::LocalTooBig | ProbeOutcome::Timeout => bad = size,
+        }
+        steps.push(step);
+    }
+
+    Ok((good, steps))
+}
+
+/// 用指定大小发送探测，直到收到回复或差错报文，或尝试次数用尽
+async fn probe(
+    target: &PingTarget,
+    options: &SocketOptions,
+    size: usize,
+    tries: u32,
+    timeout_ms: u64,
+    first_seq: u16,
+) -> Result<PmtuStep, PingError> {
+    let pinger = Pinger::with_socket_options(target.clone(), size, options.clone())?;
+
+    for i in 0..tries.max(1) {
+        let response = pinger
+            .ping_once(first_seq.wrapping_add(i as u16), timeout_ms)
+            .await;
+
+        let outcome = match response.error {
+            None => ProbeOutcome::Fits,
+            Some(PingError::FragmentationNeeded { router, mtu }) => {
+                ProbeOutcome::TooBig { router, mtu }
+            }
+            Some(PingError::SendError(e)) if e.raw_os_error() == Some(libc::EMSGSIZE) => {
+                ProbeOutcome::LocalTooBig
+            }
+            Some(PingError::Timeout) => continue,
+            // 不可达等差错说明问题与包大小无关，直接报告
+            Some(e) => return Err(e),
+        };
+
+        return Ok(PmtuStep {
+            size,
+            outcome,
+            rtt: response.rtt,
+        });
+    }
+
+    Ok(PmtuStep {
+        size,
+        outcome: ProbeOutcome::Timeout,
+        rtt: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTER: &str = "192.0.2.1";
+
+    /// 模拟一条路径：`hop_mtu`是会返回Fragmentation Needed的路由器的MTU及其报告的值，
+    /// `path_mtu`是真实的路径MTU，超过它但没有被路由器拒绝的包被静默丢弃
+    fn path(
+        hop_mtu: Option<(usize, u32)>,
+        path_mtu: usize,
+    ) -> impl FnMut(usize) -> std::future::Ready<Result<PmtuStep, PingError>> {
+        move |size| {
+            let ip_size = size + IPV4_HEADER_LEN;
+            let outcome = match hop_mtu {
+                Some((mtu, reported)) if ip_size > mtu => ProbeOutcome::TooBig {
+                    router: ROUTER.parse().unwrap(),
+                    mtu: reported,
+                },
+                _ if ip_size > path_mtu => ProbeOutcome::Timeout,
+                _ => ProbeOutcome::Fits,
+            };
+            std::future::ready(Ok(PmtuStep {
+                size,
+                outcome,
+                rtt: None,
+            }))
+        }
+    }
+
+    async fn run(
+        min_size: usize,
+        probe: impl FnMut(usize) -> std::future::Ready<Result<PmtuStep, PingError>>,
+    ) -> Result<(usize, Vec<PmtuStep>), SearchError> {
+        search(
+            min_size,
+            9000 - IPV4_HEADER_LEN,
+            IPV4_HEADER_LEN,
+            probe,
+            &mut |_| {},
+        )
+        .await
+    }
+
+    fn sizes(steps: &[PmtuStep]) -> Vec<usize> {
+        steps.iter().map(|step| step.size).collect()
+    }
+
+    #[tokio::test]
+    async fn test_hint_lands_exactly() {
+        let (largest, steps) = run(64, path(Some((1500, 1500)), 1500)).await.unwrap();
+        assert_eq!(largest, 1480);
+        // 最小包、第一次二分、按下一跳MTU直接命中
+        assert_eq!(sizes(&steps), [64, 64 + (8981 - 64) / 2, 1480]);
+    }
+
+    #[tokio::test]
+    async fn test_hint_too_large_falls_back_to_bisection() {
+        // 路由器报告1500，但后面还有一段1400的链路静默丢包
+        let (largest, steps) = run(64, path(Some((1500, 1500)), 1400)).await.unwrap();
+        assert_eq!(largest, 1380);
+        assert_eq!(steps[2].size, 1480);
+        assert_eq!(steps[2].outcome, ProbeOutcome::Timeout);
+        // 之后在最小包和1480之间继续二分，最终确认1381不能通过
+        assert!(steps
+            .iter()
+            .any(|step| step.size == 1381 && step.outcome == ProbeOutcome::Timeout));
+    }
+
+    #[tokio::test]
+    async fn test_without_frag_needed() {
+        let (largest, steps) = run(64, path(None, 1400)).await.unwrap();
+        assert_eq!(largest, 1380);
+        // 每一步都缩小区间，二分在log2(8981-64)+1步内结束
+        assert!(steps.len() <= 1 + 14);
+        assert!(steps
+            .iter()
+            .any(|step| step.size == 1381 && step.outcome == ProbeOutcome::Timeout));
+    }
+
+    #[tokio::test]
+    async fn test_minimum_size_failure() {
+        let result = run(1500, path(None, 1400)).await;
+        assert!(matches!(result, Err(SearchError::Unreachable)));
+
+        // 上界等于下界时不再探测
+        let (largest, steps) = search(64, 64, IPV4_HEADER_LEN, path(None, 1400), &mut |_| {})
+            .await
+            .unwrap();
+        assert_eq!(largest, 64);
+        assert_eq!(steps.len(), 1);
+    }
+}
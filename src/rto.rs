@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+/// 自适应超时的下限（毫秒），避免局域网内几十微秒的RTT算出过短的超时
+const MIN_TIMEOUT_MS: f64 = 10.0;
+/// 时钟粒度G（毫秒）
+const CLOCK_GRANULARITY_MS: f64 = 1.0;
+/// RTTVAR的倍数K
+const K: f64 = 4.0;
+/// SRTT的平滑系数
+const ALPHA: f64 = 1.0 / 8.0;
+/// RTTVAR的平滑系数
+const BETA: f64 = 1.0 / 4.0;
+
+/// 按RFC 6298为一个目标估计重传超时（RTO）
+///
+/// 根据成功探测的RTT维护平滑RTT（SRTT）和RTT偏差（RTTVAR），
+/// 超时取`SRTT + max(G, K * RTTVAR)`并限制在`[MIN_TIMEOUT_MS, max]`之间；
+/// 还没有样本时使用上限，探测超时后超时时间加倍，直到下一个样本重新计算
+#[derive(Debug, Clone)]
+pub struct RtoEstimator {
+    /// 平滑RTT（毫秒），还没有样本时为None
+    srtt: Option<f64>,
+    /// RTT偏差（毫秒）
+    rttvar: f64,
+    /// 当前的超时（毫秒）
+    rto: f64,
+    /// 超时的上限（毫秒），即命令行指定的超时
+    max: f64,
+}
+
+impl RtoEstimator {
+    /// 创建估计器，`max_ms`同时作为第一个探测的超时和超时的上限
+    pub fn new(max_ms: u64) -> Self {
+        let max = (max_ms as f64).max(MIN_TIMEOUT_MS);
+        Self {
+            srtt: None,
+            rttvar: 0.0,
+            rto: max,
+            max,
+        }
+    }
+
+    /// 下一个探测使用的超时（毫秒）
+    pub fn timeout_ms(&self) -> u64 {
+        self.rto.ceil() as u64
+    }
+
+    /// 用一个成功探测的RTT更新估计
+    pub fn sample(&mut self, rtt: Duration) {
+        let r = rtt.as_secs_f64() * 1000.0;
+        match self.srtt {
+            None => {
+                self.srtt = Some(r);
+                self.rttvar = r / 2.0;
+            }
+            Some(srtt) => {
+                self.rttvar = (1.0 - BETA) * self.rttvar + BETA * (srtt - r).abs();
+                self.srtt = Some((1.0 - ALPHA) * srtt + ALPHA * r);
+            }
+        }
+        let srtt = self.srtt.unwrap_or(r);
+        self.rto =
+            (srtt + (K * self.rttvar).max(CLOCK_GRANULARITY_MS)).clamp(MIN_TIMEOUT_MS, self.max);
+    }
+
+    /// 探测超时后超时时间加倍
+    pub fn timed_out(&mut self) {
+        self.rto = (self.rto * 2.0).min(self.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeout_follows_rtt_and_backs_off() {
+        let mut rto = RtoEstimator::new(5000);
+        assert_eq!(rto.timeout_ms(), 5000);
+
+        // 第一个样本：SRTT=100，RTTVAR=50，RTO=100+4*50
+        rto.sample(Duration::from_millis(100));
+        assert_eq!(rto.timeout_ms(), 300);
+
+        // RTTVAR=0.75*50+0.25*0=37.5，SRTT=100，RTO=100+150
+        rto.sample(Duration::from_millis(100));
+        assert_eq!(rto.timeout_ms(), 250);
+
+        rto.timed_out();
+        assert_eq!(rto.timeout_ms(), 500);
+
+        // 局域网内的RTT不会低于下限，卫星链路不会超过上限
+        let mut lan = RtoEstimator::new(5000);
+        lan.sample(Duration::from_micros(50));
+        assert_eq!(lan.timeout_ms(), MIN_TIMEOUT_MS as u64);
+
+        let mut satellite = RtoEstimator::new(1000);
+        satellite.sample(Duration::from_millis(600));
+        assert_eq!(satellite.timeout_ms(), 1000);
+        satellite.timed_out();
+        assert_eq!(satellite.timeout_ms(), 1000);
+    }
+}
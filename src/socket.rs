@@ -0,0 +1,527 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::SystemTime;
+
+use crate::error::PingError;
+#[cfg(target_os = "linux")]
+use crate::icmp::{IcmpErrorKind, IcmpErrorReply};
+
+/// 地址族
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    pub fn of(addr: &IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(_) => Family::V4,
+            IpAddr::V6(_) => Family::V6,
+        }
+    }
+}
+
+/// 共享socket的配置，配置相同的Pinger共用同一个socket
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SocketOptions {
+    pub family: Family,
+    pub ttl: u32,
+    /// 设置DF位（IPv6禁止源端分片），超过路径MTU的包会被拒绝而不是被分片
+    pub dont_fragment: bool,
+    /// 探测使用的源地址和网卡
+    pub binding: Binding,
+    /// 探测的DSCP，写入IPv4的TOS字段或IPv6的Traffic Class字段的高6位
+    pub dscp: u8,
+}
+
+/// 探测绑定的源地址和网卡，用于多出口主机上指定从哪条链路发出
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Binding {
+    /// 绑定的源地址（bind）
+    pub source: Option<IpAddr>,
+    /// 绑定的网卡名（SO_BINDTODEVICE）
+    pub interface: Option<String>,
+}
+
+impl Binding {
+    pub fn new(source: Option<IpAddr>, interface: Option<String>) -> Self {
+        Self { source, interface }
+    }
+
+    /// 把socket绑定到网卡和源地址，源地址的地址族必须与socket一致
+    pub fn apply(&self, socket: &Socket, family: Family) -> io::Result<()> {
+        if let Some(interface) = &self.interface {
+            bind_to_device(socket, interface)?;
+        }
+        if let Some(source) = self.source {
+            if Family::of(&source) != family {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "source address {} does not match the target's address family",
+                        source
+                    ),
+                ));
+            }
+            socket.bind(&SocketAddr::new(source, 0).into())?;
+        }
+        Ok(())
+    }
+
+    /// 获取发往目标地址时使用的源地址：指定了源地址时直接使用，
+    /// 否则由内核在绑定的网卡上选择
+    ///
+    /// 通过连接一个UDP socket实现，不会发送任何数据
+    pub fn source_for(&self, destination: IpAddr) -> Option<IpAddr> {
+        if self.source.is_some() {
+            return self.source;
+        }
+        let family = Family::of(&destination);
+        let domain = match family {
+            Family::V4 => Domain::IPV4,
+            Family::V6 => Domain::IPV6,
+        };
+        let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP)).ok()?;
+        self.apply(&socket, family).ok()?;
+        socket
+            .connect(&SocketAddr::new(destination, 9).into())
+            .ok()?;
+        socket.local_addr().ok()?.as_socket().map(|addr| addr.ip())
+    }
+}
+
+/// socket类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketKind {
+    /// SOCK_RAW，需要root权限或CAP_NET_RAW
+    Raw,
+    /// SOCK_DGRAM ping socket，Linux上由net.ipv4.ping_group_range控制，
+    /// 内核会改写ICMP标识符，且接收时不带IP头
+    Dgram,
+}
+
+/// 一次接收的结果
+pub struct Received {
+    pub len: usize,
+    pub from: IpAddr,
+    /// 从辅助数据中读到的TTL（IPv6为跳数限制），没有时为None
+    pub ttl: Option<u8>,
+    /// 内核记录的接收时间（SO_TIMESTAMPNS），不支持时为None
+    pub timestamp: Option<SystemTime>,
+}
+
+/// ICMP socket，优先使用原始套接字，权限不足时退回到无特权的数据报套接字
+pub struct IcmpSocket {
+    socket: Socket,
+    kind: SocketKind,
+}
+
+impl IcmpSocket {
+    pub fn open(options: &SocketOptions) -> Result<Self, PingError> {
+        let (domain, protocol) = match options.family {
+            Family::V4 => (Domain::IPV4, Protocol::ICMPV4),
+            Family::V6 => (Domain::IPV6, Protocol::ICMPV6),
+        };
+
+        let raw = Socket::new(domain, Type::from(libc::SOCK_RAW), Some(protocol));
+        let (socket, kind) = match raw {
+            Ok(socket) => (socket, SocketKind::Raw),
+            Err(e) if is_permission_error(&e) => {
+                // 没有root权限时尝试无特权的ping socket
+                match Socket::new(domain, Type::DGRAM, Some(protocol)) {
+                    Ok(socket) => (socket, SocketKind::Dgram),
+                    Err(e) if is_permission_error(&e) => return Err(PingError::PermissionDenied),
+                    Err(e) => return Err(PingError::SendError(e)),
+                }
+            }
+            Err(e) => return Err(PingError::SendError(e)),
+        };
+
+        match options.family {
+            Family::V4 => {
+                socket.set_ttl(options.ttl)?;
+                if kind == SocketKind::Dgram {
+                    // 数据报套接字收不到IP头，通过辅助数据获取TTL
+                    set_int_option(&socket, libc::IPPROTO_IP, libc::IP_RECVTTL, 1)?;
+                    // 数据报套接字只能通过错误队列收到ICMP差错报文
+                    #[cfg(target_os = "linux")]
+                    set_int_option(&socket, libc::IPPROTO_IP, libc::IP_RECVERR, 1)?;
+                }
+            }
+            Family::V6 => {
+                socket.set_unicast_hops_v6(options.ttl)?;
+                // ICMPv6套接字收不到IPv6头，跳数限制只能通过辅助数据获取
+                set_int_option(&socket, libc::IPPROTO_IPV6, libc::IPV6_RECVHOPLIMIT, 1)?;
+                #[cfg(target_os = "linux")]
+                if kind == SocketKind::Dgram {
+                    set_int_option(&socket, libc::IPPROTO_IPV6, libc::IPV6_RECVERR, 1)?;
+                }
+            }
+        }
+
+        if options.dont_fragment {
+            set_dont_fragment(&socket, options.family)?;
+        }
+
+        options.binding.apply(&socket, options.family)?;
+
+        if options.dscp != 0 {
+            set_dscp(&socket, options.family, options.dscp)?;
+        }
+
+        // 由内核记录接收时间，不把任务调度的延迟算进RTT；失败时退回到用户态计时
+        #[cfg(target_os = "linux")]
+        let _ = set_int_option(&socket, libc::SOL_SOCKET, libc::SO_TIMESTAMPNS, 1);
+
+        socket.set_nonblocking(true)?;
+
+        Ok(Self { socket, kind })
+    }
+
+    pub fn kind(&self) -> SocketKind {
+        self.kind
+    }
+
+    pub fn send_to(&self, packet: &[u8], addr: IpAddr) -> io::Result<usize> {
+        let socket_addr = SocketAddr::new(addr, 0);
+        self.socket.send_to(packet, &socket_addr.into())
+    }
+
+    /// 接收一个包，同时解析辅助数据
+    pub fn recv(&self, buffer: &mut [u8]) -> io::Result<Received> {
+        let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut iov = libc::iovec {
+            iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buffer.len(),
+        };
+        // 使用u64数组保证cmsghdr的对齐
+        let mut control = [0u64; 32];
+
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_name = &mut addr as *mut libc::sockaddr_storage as *mut libc::c_void;
+        msg.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = mem::size_of_val(&control) as _;
+
+        let len = unsafe { libc::recvmsg(self.socket.as_raw_fd(), &mut msg, 0) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let from = sockaddr_to_ip(&addr).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "unsupported address family")
+        })?;
+
+        let mut ttl = None;
+        #[cfg_attr(not(target_os = "linux"), allow(unused_mut))]
+        let mut timestamp = None;
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                let data = libc::CMSG_DATA(cmsg);
+                #[cfg(target_os = "linux")]
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET
+                    && (*cmsg).cmsg_type == libc::SCM_TIMESTAMPNS
+                {
+                    let ts = std::ptr::read_unaligned(data as *const libc::timespec);
+                    timestamp = Some(
+                        SystemTime::UNIX_EPOCH
+                            + std::time::Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32),
+                    );
+                }
+                if (*cmsg).cmsg_level == libc::IPPROTO_IP
+                    && ((*cmsg).cmsg_type == libc::IP_TTL || (*cmsg).cmsg_type == libc::IP_RECVTTL)
+                {
+                    ttl = Some(read_ttl(data));
+                } else if (*cmsg).cmsg_level == libc::IPPROTO_IPV6
+                    && (*cmsg).cmsg_type == libc::IPV6_HOPLIMIT
+                {
+                    ttl = Some(std::ptr::read_unaligned(data as *const i32) as u8);
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+
+        Ok(Received {
+            len: len as usize,
+            from,
+            ttl,
+            timestamp,
+        })
+    }
+}
+
+impl IcmpSocket {
+    /// 从错误队列读取一个ICMP差错（仅用于Linux的数据报套接字）
+    ///
+    /// 错误队列中的数据是我们发出的原始Echo Request，目的地址在msg_name中，
+    /// 差错类型和发送差错的路由器在IP_RECVERR辅助数据中
+    #[cfg(target_os = "linux")]
+    pub fn recv_error(&self, buffer: &mut [u8]) -> io::Result<Option<IcmpErrorReply>> {
+        let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut iov = libc::iovec {
+            iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buffer.len(),
+        };
+        let mut control = [0u64; 64];
+
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_name = &mut addr as *mut libc::sockaddr_storage as *mut libc::c_void;
+        msg.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = mem::size_of_val(&control) as _;
+
+        let len = unsafe { libc::recvmsg(self.socket.as_raw_fd(), &mut msg, libc::MSG_ERRQUEUE) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let received_at = std::time::Instant::now();
+
+        let destination = match sockaddr_to_ip(&addr) {
+            Some(destination) => destination,
+            None => return Ok(None),
+        };
+        // 原始Echo Request的头部：类型、代码、校验和、标识符、序号
+        let echo = match buffer.get(..8.min(len as usize)) {
+            Some(echo) if echo.len() == 8 => echo,
+            _ => return Ok(None),
+        };
+
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                let level = (*cmsg).cmsg_level;
+                let cmsg_type = (*cmsg).cmsg_type;
+                if (level == libc::IPPROTO_IP && cmsg_type == libc::IP_RECVERR)
+                    || (level == libc::IPPROTO_IPV6 && cmsg_type == libc::IPV6_RECVERR)
+                {
+                    let ee_ptr = libc::CMSG_DATA(cmsg) as *const libc::sock_extended_err;
+                    let ee = std::ptr::read_unaligned(ee_ptr);
+                    let kind = match ee.ee_origin {
+                        libc::SO_EE_ORIGIN_ICMP => {
+                            IcmpErrorKind::from_icmpv4(ee.ee_type, ee.ee_code)
+                        }
+                        libc::SO_EE_ORIGIN_ICMP6 => {
+                            IcmpErrorKind::from_icmpv6(ee.ee_type, ee.ee_code)
+                        }
+                        _ => None,
+                    };
+                    let router = sockaddr_ptr_to_ip(libc::SO_EE_OFFENDER(ee_ptr));
+
+                    if let (Some(kind), Some(router)) = (kind, router) {
+                        return Ok(Some(IcmpErrorReply {
+                            kind,
+                            router,
+                            mtu: match kind {
+                                IcmpErrorKind::FragmentationNeeded => Some(ee.ee_info),
+                                _ => None,
+                            },
+                            identifier: u16::from_be_bytes([echo[4], echo[5]]),
+                            sequence: u16::from_be_bytes([echo[6], echo[7]]),
+                            destination,
+                            received_at,
+                        }));
+                    }
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl AsRawFd for IcmpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+fn is_permission_error(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::PermissionDenied
+}
+
+fn set_int_option(socket: &Socket, level: i32, name: i32, value: i32) -> io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const i32 as *const libc::c_void,
+            mem::size_of::<i32>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// 只从指定的网卡收发（SO_BINDTODEVICE），Linux以外的系统不支持
+fn bind_to_device(socket: &Socket, interface: &str) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_BINDTODEVICE,
+                interface.as_ptr() as *const libc::c_void,
+                interface.len() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (socket, interface);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "binding to an interface is only supported on Linux",
+        ))
+    }
+}
+
+/// 设置IPv4的TOS或IPv6的Traffic Class，ECN位保持为0
+fn set_dscp(socket: &Socket, family: Family, dscp: u8) -> io::Result<()> {
+    let tos = (dscp as i32) << 2;
+    match family {
+        Family::V4 => set_int_option(socket, libc::IPPROTO_IP, libc::IP_TOS, tos),
+        Family::V6 => set_int_option(socket, libc::IPPROTO_IPV6, libc::IPV6_TCLASS, tos),
+    }
+}
+
+/// 解析DSCP，接受0-63的数字或标准名称（be、ef、cs0-cs7、af11-af43、va、le）
+pub fn parse_dscp(value: &str) -> Result<u8, String> {
+    let invalid = || format!("invalid DSCP: {}", value);
+    let name = value.trim().to_ascii_lowercase();
+    let dscp = match name.as_str() {
+        "be" | "df" => 0,
+        "le" => 1,
+        "ef" => 46,
+        "va" => 44,
+        _ => {
+            if let Some(class) = name.strip_prefix("cs") {
+                match class.parse::<u8>() {
+                    Ok(class) if class <= 7 => class << 3,
+                    _ => return Err(invalid()),
+                }
+            } else if let Some(af) = name.strip_prefix("af") {
+                // AFxy：x为类别1-4，y为丢弃优先级1-3
+                let digits: Vec<u8> = af.bytes().map(|b| b.wrapping_sub(b'0')).collect();
+                match digits[..] {
+                    [class @ 1..=4, drop @ 1..=3] => (class << 3) | (drop << 1),
+                    _ => return Err(invalid()),
+                }
+            } else {
+                match name.parse::<u8>() {
+                    Ok(dscp) if dscp <= 63 => dscp,
+                    _ => return Err(invalid()),
+                }
+            }
+        }
+    };
+    Ok(dscp)
+}
+
+/// 解析逗号分隔的DSCP列表，去掉重复的值并保持顺序
+pub fn parse_dscp_list(list: &str) -> Result<Vec<u8>, String> {
+    let mut classes = Vec::new();
+    for value in list.split(',') {
+        let dscp = parse_dscp(value)?;
+        if !classes.contains(&dscp) {
+            classes.push(dscp);
+        }
+    }
+    Ok(classes)
+}
+
+/// 禁止分片：Linux上使用IP_PMTUDISC_DO，超过已知路径MTU的包在send时返回EMSGSIZE，
+/// 其他系统上使用IP_DONTFRAG/IPV6_DONTFRAG
+fn set_dont_fragment(socket: &Socket, family: Family) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    match family {
+        Family::V4 => set_int_option(
+            socket,
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_DO,
+        ),
+        Family::V6 => set_int_option(
+            socket,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MTU_DISCOVER,
+            libc::IPV6_PMTUDISC_DO,
+        ),
+    }
+    #[cfg(not(target_os = "linux"))]
+    match family {
+        Family::V4 => set_int_option(socket, libc::IPPROTO_IP, libc::IP_DONTFRAG, 1),
+        // RFC 3542中IPV6_DONTFRAG的取值，libc没有为所有平台导出
+        Family::V6 => set_int_option(socket, libc::IPPROTO_IPV6, 62, 1),
+    }
+}
+
+/// Linux上IP_TTL辅助数据是int，BSD/macOS上IP_RECVTTL是单个字节
+unsafe fn read_ttl(data: *const u8) -> u8 {
+    #[cfg(target_os = "linux")]
+    {
+        std::ptr::read_unaligned(data as *const i32) as u8
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        *data
+    }
+}
+
+fn sockaddr_to_ip(addr: &libc::sockaddr_storage) -> Option<IpAddr> {
+    unsafe { sockaddr_ptr_to_ip(addr as *const _ as *const libc::sockaddr) }
+}
+
+unsafe fn sockaddr_ptr_to_ip(addr: *const libc::sockaddr) -> Option<IpAddr> {
+    match (*addr).sa_family as i32 {
+        libc::AF_INET => {
+            let addr = std::ptr::read_unaligned(addr as *const libc::sockaddr_in);
+            Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                addr.sin_addr.s_addr,
+            ))))
+        }
+        libc::AF_INET6 => {
+            let addr = std::ptr::read_unaligned(addr as *const libc::sockaddr_in6);
+            Some(IpAddr::V6(Ipv6Addr::from(addr.sin6_addr.s6_addr)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dscp_names_and_numbers() {
+        assert_eq!(parse_dscp("ef"), Ok(46));
+        assert_eq!(parse_dscp("AF41"), Ok(34));
+        assert_eq!(parse_dscp("af11"), Ok(10));
+        assert_eq!(parse_dscp("cs6"), Ok(48));
+        assert_eq!(parse_dscp("be"), Ok(0));
+        assert_eq!(parse_dscp("63"), Ok(63));
+        assert!(parse_dscp("64").is_err());
+        assert!(parse_dscp("af44").is_err());
+        assert!(parse_dscp("cs8").is_err());
+
+        assert_eq!(parse_dscp_list("0,ef,46"), Ok(vec![0, 46]));
+        assert!(parse_dscp_list("0,").is_err());
+    }
+}
@@ -1,13 +1,64 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
 use std::time::Duration;
 
+use crate::error::{ExitStatus, PingError};
+use crate::histogram::LatencyHistogram;
+use crate::host::{PingResponse, Probe};
+use crate::icmp::{IcmpErrorKind, PayloadCheck, TimestampSource};
+use crate::tracker::ReplyClass;
+
 #[derive(Debug, Clone)]
 pub struct PingStats {
+    /// 发出的数据包数，包括重试
     pub sent: u32,
+    /// 收到回复的数据包数
     pub received: u32,
+    /// 已结束的逻辑探测数，一个探测及其全部重试算作一个
+    pub probes: u32,
+    /// 收到回复的逻辑探测数，无论是第一次发送还是重试收到的
+    pub probes_answered: u32,
+    /// 重试消耗的数据包数
+    pub retries: u32,
+    /// 至少重试过一次的逻辑探测数
+    pub probes_retried: u32,
     pub min_rtt: Option<Duration>,
     pub max_rtt: Option<Duration>,
     pub sum_rtt: Duration,
+    /// RTT平方和（秒²），用于计算标准差
+    pub sum_sq_rtt: f64,
+    pub last_rtt: Option<Duration>,
     pub last_seq: u16,
+    /// RTT的分布，用于计算分位数
+    pub histogram: LatencyHistogram,
+    /// 按RFC 3550平滑的相邻RTT之差（秒）
+    pub jitter: f64,
+    /// 按种类统计的ICMP差错报文数量
+    pub icmp_errors: HashMap<IcmpErrorKind, u32>,
+    /// 回显数据被改写的回复数
+    pub corrupted: u32,
+    /// 回显数据被截断的回复数
+    pub truncated: u32,
+    /// 重复的回复数
+    pub duplicates: u32,
+    /// 超时之后才到达的回复数
+    pub late: u32,
+    /// 比后发的探测的回复更晚到达的回复数
+    pub out_of_order: u32,
+    /// RTT取自内核接收时间戳的回复数
+    pub kernel_timestamps: u32,
+    /// TCP探测收到RST（主机在线、端口关闭）的次数，计入已接收
+    pub port_closed: u32,
+    /// 带有对端时钟估算的时间戳应答数
+    pub clock_samples: u32,
+    /// 去程单向延迟之和（毫秒，包含时钟偏差）
+    pub sum_forward_ms: i64,
+    /// 回程单向延迟之和（毫秒，包含时钟偏差）
+    pub sum_return_ms: i64,
+    /// 探测使用的源地址
+    pub source: Option<IpAddr>,
+    /// 探测使用的DSCP，未指定时为None
+    pub dscp: Option<u8>,
 }
 
 impl PingStats {
@@ -15,10 +66,31 @@ impl PingStats {
         PingStats {
             sent: 0,
             received: 0,
+            probes: 0,
+            probes_answered: 0,
+            retries: 0,
+            probes_retried: 0,
             min_rtt: None,
             max_rtt: None,
             sum_rtt: Duration::from_secs(0),
+            sum_sq_rtt: 0.0,
+            last_rtt: None,
             last_seq: 0,
+            histogram: LatencyHistogram::new(),
+            jitter: 0.0,
+            icmp_errors: HashMap::new(),
+            corrupted: 0,
+            truncated: 0,
+            duplicates: 0,
+            late: 0,
+            out_of_order: 0,
+            kernel_timestamps: 0,
+            port_closed: 0,
+            clock_samples: 0,
+            sum_forward_ms: 0,
+            sum_return_ms: 0,
+            source: None,
+            dscp: None,
         }
     }
 
@@ -27,6 +99,14 @@ impl PingStats {
         self.received += 1;
         self.last_seq = seq;
         self.sum_rtt += rtt;
+        self.sum_sq_rtt += rtt.as_secs_f64() * rtt.as_secs_f64();
+        self.histogram.record(rtt);
+        if let Some(last_rtt) = self.last_rtt {
+            // RFC 3550 6.4.1：J += (|D| - J) / 16，D为相邻两次的传输时间之差
+            let d = (rtt.as_secs_f64() - last_rtt.as_secs_f64()).abs();
+            self.jitter += (d - self.jitter) / 16.0;
+        }
+        self.last_rtt = Some(rtt);
 
         if let Some(min_rtt) = self.min_rtt {
             if rtt < min_rtt {
@@ -45,9 +125,108 @@ impl PingStats {
         }
     }
 
-    pub fn update_with_failure(&mut self, seq: u16) {
+    pub fn update_with_failure(&mut self, seq: u16, error: &PingError) {
         self.sent += 1;
         self.last_seq = seq;
+
+        if let Some(kind) = error.icmp_error_kind() {
+            *self.icmp_errors.entry(kind).or_insert(0) += 1;
+        }
+    }
+
+    /// 记录一次发送所属的逻辑探测，`attempt`大于0的发送是重试；
+    /// 不属于任何逻辑探测的响应（迟到和重复的回复）不计入
+    pub fn record_attempt(&mut self, response: &PingResponse) {
+        if response.probe_seq.is_none() {
+            return;
+        }
+        if response.attempt > 0 {
+            self.retries += 1;
+        }
+        if response.final_attempt {
+            self.probes += 1;
+            if response.is_success() {
+                self.probes_answered += 1;
+            }
+            if response.attempt > 0 {
+                self.probes_retried += 1;
+            }
+        }
+    }
+
+    /// 记录一个回复的数据校验结果，损坏的回复仍计入已接收
+    pub fn record_payload_check(&mut self, check: PayloadCheck) {
+        match check {
+            PayloadCheck::Intact => {}
+            PayloadCheck::Truncated => self.truncated += 1,
+            PayloadCheck::Corrupted => self.corrupted += 1,
+        }
+    }
+
+    /// 记录一个回复的到达情况，迟到和重复的回复不计入已接收
+    pub fn record_reply_class(&mut self, class: ReplyClass) {
+        match class {
+            ReplyClass::OnTime => {}
+            ReplyClass::OutOfOrder => self.out_of_order += 1,
+            ReplyClass::Late => self.late += 1,
+            ReplyClass::Duplicate => self.duplicates += 1,
+        }
+    }
+
+    pub fn record_timestamp_source(&mut self, source: TimestampSource) {
+        if source == TimestampSource::Kernel {
+            self.kernel_timestamps += 1;
+        }
+    }
+
+    /// 记录探测使用的源地址，未知时保留之前记录的地址
+    pub fn record_source(&mut self, source: Option<IpAddr>) {
+        if source.is_some() {
+            self.source = source;
+        }
+    }
+
+    /// 记录一个成功响应的探测方式
+    pub fn record_probe(&mut self, probe: Probe) {
+        match probe {
+            Probe::Tcp { open: false, .. } => self.port_closed += 1,
+            Probe::Timestamp { clock: Some(clock) } => {
+                self.clock_samples += 1;
+                self.sum_forward_ms += clock.forward_ms as i64;
+                self.sum_return_ms += clock.return_ms as i64;
+            }
+            _ => {}
+        }
+    }
+
+    /// 平均去程时延（毫秒），包含对端的时钟偏差
+    pub fn forward_delay_ms(&self) -> Option<f64> {
+        (self.clock_samples > 0).then(|| self.sum_forward_ms as f64 / self.clock_samples as f64)
+    }
+
+    /// 平均回程时延（毫秒），减去了对端的时钟偏差
+    pub fn return_delay_ms(&self) -> Option<f64> {
+        (self.clock_samples > 0).then(|| self.sum_return_ms as f64 / self.clock_samples as f64)
+    }
+
+    /// 对端时钟相对本机的平均偏差（毫秒），正值表示对端时钟偏快
+    pub fn clock_offset_ms(&self) -> Option<f64> {
+        Some((self.forward_delay_ms()? - self.return_delay_ms()?) / 2.0)
+    }
+
+    /// RTT的时间来源：全部来自内核、全部来自用户态，或两者都有
+    pub fn rtt_source(&self) -> &'static str {
+        if self.kernel_timestamps == 0 {
+            TimestampSource::Userspace.name()
+        } else if self.kernel_timestamps >= self.received {
+            TimestampSource::Kernel.name()
+        } else {
+            "mixed"
+        }
+    }
+
+    pub fn icmp_error_count(&self, kind: IcmpErrorKind) -> u32 {
+        self.icmp_errors.get(&kind).copied().unwrap_or(0)
     }
 
     pub fn avg_rtt(&self) -> Option<Duration> {
@@ -58,6 +237,31 @@ impl PingStats {
         }
     }
 
+    /// RTT的总体标准差
+    pub fn stddev_rtt(&self) -> Option<Duration> {
+        if self.received == 0 {
+            return None;
+        }
+        let n = self.received as f64;
+        let mean = self.sum_rtt.as_secs_f64() / n;
+        let variance = (self.sum_sq_rtt / n - mean * mean).max(0.0);
+        Some(Duration::from_secs_f64(variance.sqrt()))
+    }
+
+    /// RFC 3550的到达间隔抖动，至少需要两个回复
+    pub fn jitter(&self) -> Option<Duration> {
+        if self.received >= 2 {
+            Some(Duration::from_secs_f64(self.jitter))
+        } else {
+            None
+        }
+    }
+
+    /// 第`percentile`百分位的RTT
+    pub fn percentile_rtt(&self, percentile: f64) -> Option<Duration> {
+        self.histogram.percentile(percentile)
+    }
+
     pub fn loss_percent(&self) -> f64 {
         if self.sent > 0 {
             (1.0 - (self.received as f64 / self.sent as f64)) * 100.0
@@ -65,4 +269,114 @@ impl PingStats {
             0.0
         }
     }
+
+    /// 逻辑探测的成功率，重试后收到回复的探测也算成功
+    pub fn probe_success_percent(&self) -> f64 {
+        if self.probes > 0 {
+            self.probes_answered as f64 / self.probes as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    /// 按丢失率阈值判断目标是否可达：未指定阈值时只要收到过回复即可达，
+    /// 否则失败的逻辑探测超过`max_loss`百分比即视为不可达，重试成功的探测不算丢失
+    pub fn is_reachable(&self, max_loss: Option<f64>) -> bool {
+        match max_loss {
+            Some(max_loss) => self.received > 0 && 100.0 - self.probe_success_percent() <= max_loss,
+            None => self.received > 0,
+        }
+    }
+}
+
+/// 合并设置阶段的问题和各目标的可达情况，得到进程的退出码
+///
+/// `setup`记录主机名解析失败、参数无效等问题；没有任何统计的目标视为不可达
+pub fn exit_status(
+    setup: ExitStatus,
+    targets: &[String],
+    stats: &HashMap<String, PingStats>,
+    max_loss: Option<f64>,
+) -> ExitStatus {
+    let all_reachable = targets.iter().all(|name| {
+        stats
+            .get(name)
+            .is_some_and(|stats| stats.is_reachable(max_loss))
+    });
+    if all_reachable {
+        setup
+    } else {
+        setup.max(ExitStatus::Unreachable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::PingTarget;
+
+    /// 发送`sent`个探测、收到`received`个回复的统计
+    fn stats(sent: u32, received: u32) -> PingStats {
+        let target = PingTarget::new("a".to_string(), "127.0.0.1".parse().unwrap());
+        let mut stats = PingStats::new();
+        for seq in 0..sent as u16 {
+            let mut response = if (seq as u32) < received {
+                stats.update_with_success(seq, Duration::from_millis(1));
+                PingResponse::success(target.clone(), seq, Duration::from_millis(1), 64, 64)
+            } else {
+                stats.update_with_failure(seq, &PingError::Timeout);
+                PingResponse::failure(target.clone(), seq, 64, 64, PingError::Timeout)
+            };
+            response.probe_seq = Some(seq as u64);
+            stats.record_attempt(&response);
+        }
+        stats
+    }
+
+    #[test]
+    fn test_exit_status() {
+        let targets = vec!["a".to_string(), "b".to_string()];
+        let mut all = HashMap::new();
+        all.insert("a".to_string(), stats(4, 4));
+        all.insert("b".to_string(), stats(4, 3));
+
+        // 部分丢包仍然可达
+        assert_eq!(
+            exit_status(ExitStatus::AllReachable, &targets, &all, None),
+            ExitStatus::AllReachable
+        );
+
+        // 超过--max-loss阈值即不可达
+        assert_eq!(
+            exit_status(ExitStatus::AllReachable, &targets, &all, Some(0.0)),
+            ExitStatus::Unreachable
+        );
+        assert_eq!(
+            exit_status(ExitStatus::AllReachable, &targets, &all, Some(25.0)),
+            ExitStatus::AllReachable
+        );
+
+        // 全部丢失或没有任何统计的目标不可达
+        let mut partial = all.clone();
+        partial.insert("b".to_string(), stats(4, 0));
+        assert_eq!(
+            exit_status(ExitStatus::AllReachable, &targets, &partial, None),
+            ExitStatus::Unreachable
+        );
+        partial.remove("b");
+        assert_eq!(
+            exit_status(ExitStatus::AllReachable, &targets, &partial, None),
+            ExitStatus::Unreachable
+        );
+
+        // 主机名无法解析优先于不可达
+        assert_eq!(
+            exit_status(ExitStatus::UnresolvedHost, &targets, &all, None),
+            ExitStatus::UnresolvedHost
+        );
+        assert_eq!(
+            exit_status(ExitStatus::UnresolvedHost, &targets, &partial, None),
+            ExitStatus::UnresolvedHost
+        );
+    }
 }
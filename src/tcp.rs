@@ -0,0 +1,173 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+use tokio::net::TcpSocket;
+use tokio::sync::mpsc;
+use tokio::time;
+
+use crate::error::PingError;
+use crate::host::{PingResponse, PingTarget, Probe};
+use crate::pacer;
+use crate::pinger::{run_probes, RetryPolicy};
+use crate::socket::{Binding, Family};
+
+/// TcpPinger通过TCP握手探测主机，用于丢弃ICMP但放行TCP端口的防火墙之后的主机
+///
+/// 收到SYN-ACK说明端口开放，收到RST说明主机在线但端口关闭，两者都算成功；
+/// 每个探测使用独立的socket，不需要root权限
+pub struct TcpPinger {
+    /// 目标主机的信息
+    target: PingTarget,
+    /// 目标端口
+    port: u16,
+    /// TTL值
+    ttl: u32,
+    /// 绑定的源地址和网卡
+    binding: Binding,
+    /// 发往目标时使用的源地址
+    source: Option<IpAddr>,
+}
+
+impl TcpPinger {
+    /// 创建一个新的TcpPinger
+    ///
+    /// # 参数
+    ///
+    /// * `target`: 目标主机的信息
+    /// * `port`: 探测的端口
+    /// * `ttl`: TTL值
+    /// * `binding`: 发起连接的源地址和网卡
+    pub fn new(target: PingTarget, port: u16, ttl: u32, binding: &Binding) -> Self {
+        let source = binding.source_for(target.addr);
+        Self {
+            target,
+            port,
+            ttl,
+            binding: binding.clone(),
+            source,
+        }
+    }
+
+    fn open_socket(&self) -> io::Result<TcpSocket> {
+        let domain = match self.target.addr {
+            IpAddr::V4(_) => Domain::IPV4,
+            IpAddr::V6(_) => Domain::IPV6,
+        };
+        let socket = Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?;
+        match self.target.addr {
+            IpAddr::V4(_) => socket.set_ttl(self.ttl)?,
+            IpAddr::V6(_) => socket.set_unicast_hops_v6(self.ttl)?,
+        }
+        // 关闭时直接发送RST，不在本机留下TIME_WAIT连接
+        socket.set_linger(Some(Duration::ZERO))?;
+        self.binding.apply(&socket, Family::of(&self.target.addr))?;
+        socket.set_nonblocking(true)?;
+        Ok(TcpSocket::from_std_stream(TcpStream::from(socket)))
+    }
+
+    /// 发起一次TCP连接并等待SYN-ACK或RST
+    ///
+    /// # 参数
+    ///
+    /// * `seq`: 探测的序号，只用于输出
+    /// * `timeout_ms`: 等待握手的超时时间（毫秒）
+    pub async fn ping_once(&self, seq: u16, timeout_ms: u64) -> PingResponse {
+        pacer::wait().await;
+        let socket = match self.open_socket() {
+            Ok(socket) => socket,
+            Err(e) => {
+                return PingResponse::failure(
+                    self.target.clone(),
+                    seq,
+                    0,
+                    self.ttl as u8,
+                    PingError::SendError(e),
+                )
+            }
+        };
+
+        let addr = SocketAddr::new(self.target.addr, self.port);
+        let start = Instant::now();
+        let result = time::timeout(Duration::from_millis(timeout_ms), socket.connect(addr)).await;
+        let rtt = start.elapsed();
+
+        // 只有握手完成（SYN-ACK）时端口才算开放
+        let mut open = false;
+        let mut response = match result {
+            Ok(Ok(_stream)) => {
+                open = true;
+                PingResponse::success(self.target.clone(), seq, rtt, 0, self.ttl as u8)
+            }
+            // RST：主机在线，端口关闭
+            Ok(Err(e)) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                PingResponse::success(self.target.clone(), seq, rtt, 0, self.ttl as u8)
+            }
+            Ok(Err(e)) => PingResponse::failure(
+                self.target.clone(),
+                seq,
+                0,
+                self.ttl as u8,
+                PingError::SendError(e),
+            ),
+            Err(_) => PingResponse::failure(
+                self.target.clone(),
+                seq,
+                0,
+                self.ttl as u8,
+                PingError::Timeout,
+            ),
+        };
+        response.probe = Probe::Tcp {
+            port: self.port,
+            open,
+        };
+        response.source = self.source;
+        response
+    }
+
+    /// 发送多个TCP探测，参数与`Pinger::ping_multiple`相同
+    pub async fn ping_multiple(
+        &self,
+        count: u32,
+        period_ms: u64,
+        timeout_ms: u64,
+        retry: RetryPolicy,
+        tx: mpsc::Sender<PingResponse>,
+    ) -> Result<(), PingError> {
+        run_probes(
+            count,
+            period_ms,
+            timeout_ms,
+            retry,
+            tx,
+            |seq, timeout_ms| self.ping_once(seq, timeout_ms),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_open_and_closed_ports_are_up() {
+        let target = PingTarget::new("localhost".to_string(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let pinger = TcpPinger::new(target.clone(), port, 64, &Binding::default());
+        let response = pinger.ping_once(0, 1000).await;
+        assert!(response.is_success());
+        assert!(matches!(response.probe, Probe::Tcp { open: true, .. }));
+
+        // 关闭监听后同一端口回复RST，主机仍算在线
+        drop(listener);
+        let response = pinger.ping_once(1, 1000).await;
+        assert!(response.is_success());
+        assert!(matches!(response.probe, Probe::Tcp { open: false, .. }));
+    }
+}
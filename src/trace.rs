@@ -0,0 +1,155 @@
+use futures::future::join_all;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::error::PingError;
+use crate::host::{resolve_hostname, PingTarget};
+use crate::pinger::Pinger;
+use crate::socket::Binding;
+
+/// 一跳的探测结果
+#[derive(Debug, Clone)]
+pub struct Hop {
+    /// 跳数（即探测使用的TTL）
+    pub ttl: u32,
+    /// 这一跳的应答者，等价多路径时可能有多个
+    pub addrs: Vec<IpAddr>,
+    /// 第一个应答者的反向解析名称
+    pub hostname: Option<String>,
+    /// 发出的探测数
+    pub sent: u32,
+    /// 收到应答的RTT
+    pub rtts: Vec<Duration>,
+    /// 应答者返回了除超时以外的ICMP差错（如不可达），路径到此为止
+    pub error: Option<String>,
+}
+
+impl Hop {
+    pub fn min_rtt(&self) -> Option<Duration> {
+        self.rtts.iter().min().copied()
+    }
+
+    pub fn max_rtt(&self) -> Option<Duration> {
+        self.rtts.iter().max().copied()
+    }
+
+    pub fn avg_rtt(&self) -> Option<Duration> {
+        if self.rtts.is_empty() {
+            None
+        } else {
+            Some(self.rtts.iter().sum::<Duration>() / self.rtts.len() as u32)
+        }
+    }
+}
+
+/// 一次traceroute的结果
+#[derive(Debug, Clone)]
+pub struct TraceResult {
+    pub target: PingTarget,
+    pub max_hops: u32,
+    pub size: usize,
+    pub hops: Vec<Hop>,
+    /// 是否收到了目标本身的Echo Reply
+    pub reached: bool,
+}
+
+/// 逐跳增加TTL发送探测，收集每一跳返回Time Exceeded的路由器
+///
+/// # 参数
+///
+/// * `target`: 目标主机
+/// * `max_hops`: 最大跳数
+/// * `probes`: 每一跳发送的探测数
+/// * `timeout_ms`: 每个探测的超时时间（毫秒）
+/// * `size`: ICMP包的大小
+/// * `binding`: 绑定的源地址和网卡
+/// * `resolve`: 是否对每一跳做反向DNS解析
+/// * `on_hop`: 每完成一跳时调用，用于实时输出
+#[allow(clippy::too_many_arguments)]
+pub async fn trace(
+    target: PingTarget,
+    max_hops: u32,
+    probes: u32,
+    timeout_ms: u64,
+    size: usize,
+    binding: &Binding,
+    resolve: bool,
+    mut on_hop: impl FnMut(&Hop),
+) -> Result<TraceResult, PingError> {
+    let mut hops = Vec::new();
+    let mut reached = false;
+
+    for ttl in 1..=max_hops {
+        let mut hop = probe_hop(&target, ttl, probes, timeout_ms, size, binding).await?;
+        if resolve {
+            if let Some(addr) = hop.addrs.first() {
+                hop.hostname = resolve_hostname(*addr).await;
+            }
+        }
+        on_hop(&hop);
+
+        let hop_reached = hop.addrs.contains(&target.addr) && hop.error.is_none();
+        let stop = hop_reached || hop.error.is_some();
+        hops.push(hop);
+
+        if stop {
+            reached = hop_reached;
+            break;
+        }
+    }
+
+    Ok(TraceResult {
+        target,
+        max_hops,
+        size,
+        hops,
+        reached,
+    })
+}
+
+/// 向某一跳并行发送多个探测
+pub async fn probe_hop(
+    target: &PingTarget,
+    ttl: u32,
+    probes: u32,
+    timeout_ms: u64,
+    size: usize,
+    binding: &Binding,
+) -> Result<Hop, PingError> {
+    let pinger = Pinger::new(target.clone(), size, ttl, binding)?;
+    let responses = join_all((0..probes).map(|i| pinger.ping_once(i as u16, timeout_ms))).await;
+
+    let mut hop = Hop {
+        ttl,
+        addrs: Vec::new(),
+        hostname: None,
+        sent: probes,
+        rtts: Vec::new(),
+        error: None,
+    };
+
+    for response in responses {
+        let responder = match response.responder() {
+            Some(responder) => responder,
+            // 超时或发送失败，这一个探测记为 *
+            None => continue,
+        };
+
+        if !hop.addrs.contains(&responder) {
+            hop.addrs.push(responder);
+        }
+        if let Some(rtt) = response.rtt {
+            hop.rtts.push(rtt);
+        }
+        match &response.error {
+            None | Some(PingError::TtlExceeded(_)) => {}
+            Some(e) => {
+                hop.error = e
+                    .icmp_error_kind()
+                    .map(|kind| kind.description().to_string())
+            }
+        }
+    }
+
+    Ok(hop)
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::host::{PingResponse, PingTarget, Probe};
+use crate::icmp::IcmpEchoReply;
+
+/// 回复相对于探测的到达情况
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyClass {
+    /// 在超时之前到达
+    OnTime,
+    /// 在超时之前到达，但比后发的探测的回复更晚
+    OutOfOrder,
+    /// 探测已经超时之后才到达
+    Late,
+    /// 同一个探测的第二个及以后的回复
+    Duplicate,
+}
+
+impl ReplyClass {
+    /// 是否计入已接收的回复，迟到和重复的回复单独统计
+    pub fn is_counted(&self) -> bool {
+        matches!(self, ReplyClass::OnTime | ReplyClass::OutOfOrder)
+    }
+}
+
+/// 保留已结束探测的序号范围：比最新发出的序号落后这么多的记录会被清理，
+/// 这样持续探测时记录不会无限增长，序号回绕后旧的回复也不会匹配到新的探测
+const TRACKED_WINDOW: u16 = 4096;
+
+/// Pinger和分发器共同持有的跟踪器
+pub type SharedTracker = Arc<Mutex<ReplyTracker>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProbeState {
+    Pending,
+    Answered,
+    Expired,
+}
+
+struct ProbeRecord {
+    sent_at: Instant,
+    state: ProbeState,
+}
+
+/// 记录一个目标的每个探测的状态，用于识别迟到、重复和乱序的回复
+///
+/// 接收方把回复交给等待方之前先调用`delivered`标记探测已回复，Pinger随后用`answered`
+/// 计算回复是否乱序；没有等待方的回复交给`unmatched`，按探测的状态分为迟到或重复，
+/// 等待方刚刚放弃的回复交给`abandoned`，都通过sink以PingResponse的形式报告
+pub struct ReplyTracker {
+    target: PingTarget,
+    probes: HashMap<u16, ProbeRecord>,
+    /// 已按时收到回复的最大序号（按序号回绕比较）
+    highest_answered: Option<u16>,
+    sink: Option<mpsc::Sender<PingResponse>>,
+    /// 报告的响应所属的探测方式
+    probe: Probe,
+}
+
+impl ReplyTracker {
+    pub fn new(target: PingTarget) -> Self {
+        Self {
+            target,
+            probes: HashMap::new(),
+            highest_answered: None,
+            sink: None,
+            probe: Probe::Icmp,
+        }
+    }
+
+    /// 设置报告的响应所属的探测方式，默认为ICMP
+    pub fn set_probe(&mut self, probe: Probe) {
+        self.probe = probe;
+    }
+
+    /// 设置迟到和重复回复的接收方，为None时只更新状态
+    pub fn set_sink(&mut self, sink: Option<mpsc::Sender<PingResponse>>) {
+        self.sink = sink;
+    }
+
+    /// 记录一个探测已发出，并清理落在窗口之外的已结束探测
+    pub fn sent(&mut self, seq: u16, sent_at: Instant) {
+        self.probes.insert(
+            seq,
+            ProbeRecord {
+                sent_at,
+                state: ProbeState::Pending,
+            },
+        );
+        // 记录数超过窗口的两倍时才清理，均摊到每次发送的开销是常数
+        if self.probes.len() > 2 * TRACKED_WINDOW as usize {
+            self.probes.retain(|probe_seq, probe| {
+                probe.state == ProbeState::Pending || seq.wrapping_sub(*probe_seq) < TRACKED_WINDOW
+            });
+        }
+    }
+
+    /// 标记探测已收到回复，必须在把回复交给等待方之前调用，
+    /// 这样等待方处理之前到达的同序号回复也会被识别为重复
+    pub fn delivered(&mut self, seq: u16) {
+        if let Some(probe) = self.probes.get_mut(&seq) {
+            probe.state = ProbeState::Answered;
+        }
+    }
+
+    /// 计算一个在超时之前到达的回复是按时还是乱序到达的
+    pub fn answered(&mut self, seq: u16) -> ReplyClass {
+        match self.highest_answered {
+            Some(highest) if is_before(seq, highest) => ReplyClass::OutOfOrder,
+            _ => {
+                self.highest_answered = Some(seq);
+                ReplyClass::OnTime
+            }
+        }
+    }
+
+    /// 记录一个探测已超时
+    pub fn expired(&mut self, seq: u16) {
+        if let Some(probe) = self.probes.get_mut(&seq) {
+            if probe.state == ProbeState::Pending {
+                probe.state = ProbeState::Expired;
+            }
+        }
+    }
+
+    /// 处理没有等待方的回复，忽略不是本目标发出的序号
+    pub fn unmatched(&mut self, reply: &IcmpEchoReply) {
+        let class = match self.probes.get(&reply.sequence).map(|probe| probe.state) {
+            // 等待方刚好在超时和取消之间，仍按迟到处理
+            Some(ProbeState::Expired | ProbeState::Pending) => ReplyClass::Late,
+            Some(ProbeState::Answered) => ReplyClass::Duplicate,
+            None => return,
+        };
+        self.report_unmatched(reply, class);
+    }
+
+    /// 处理已经标记为已回复、但等待方刚刚超时放弃的回复，按迟到报告
+    pub fn abandoned(&mut self, reply: &IcmpEchoReply) {
+        if self.probes.contains_key(&reply.sequence) {
+            self.report_unmatched(reply, ReplyClass::Late);
+        }
+    }
+
+    fn report_unmatched(&mut self, reply: &IcmpEchoReply, class: ReplyClass) {
+        if let Some(probe) = self.probes.get_mut(&reply.sequence) {
+            probe.state = ProbeState::Answered;
+            let rtt = reply.received_at.saturating_duration_since(probe.sent_at);
+            self.report(reply, rtt, class);
+        }
+    }
+
+    fn report(&self, reply: &IcmpEchoReply, rtt: Duration, class: ReplyClass) {
+        if let Some(sink) = &self.sink {
+            let mut response = PingResponse::success(
+                self.target.clone(),
+                reply.sequence,
+                rtt,
+                reply.size,
+                reply.ttl,
+            );
+            response.class = class;
+            response.timestamp_source = reply.timestamp_source;
+            response.probe = self.probe;
+            // 分发器的接收任务不能阻塞，通道满时丢弃这条报告
+            let _ = sink.try_send(response);
+        }
+    }
+}
+
+/// 按RFC 1982的序号算术判断a是否在b之前
+fn is_before(a: u16, b: u16) -> bool {
+    (a.wrapping_sub(b) as i16) < 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icmp::TimestampSource;
+
+    fn echo(sequence: u16, received_at: Instant) -> IcmpEchoReply {
+        IcmpEchoReply {
+            identifier: 1,
+            sequence,
+            ttl: 64,
+            size: 64,
+            payload: Vec::new(),
+            received_at,
+            timestamp_source: TimestampSource::Userspace,
+        }
+    }
+
+    #[test]
+    fn test_classifies_late_duplicate_and_out_of_order() {
+        let target = PingTarget::new("localhost".to_string(), "127.0.0.1".parse().unwrap());
+        let (tx, mut rx) = mpsc::channel(8);
+        let mut tracker = ReplyTracker::new(target);
+        tracker.set_sink(Some(tx));
+
+        let start = Instant::now();
+        tracker.sent(0, start);
+        tracker.sent(1, start);
+        tracker.sent(u16::MAX, start);
+
+        tracker.delivered(1);
+        assert_eq!(tracker.answered(1), ReplyClass::OnTime);
+        tracker.delivered(u16::MAX);
+        assert_eq!(tracker.answered(u16::MAX), ReplyClass::OutOfOrder);
+
+        tracker.expired(0);
+        tracker.unmatched(&echo(0, start + Duration::from_millis(1500)));
+        let late = rx.try_recv().unwrap();
+        assert_eq!(late.class, ReplyClass::Late);
+        assert_eq!(late.rtt, Some(Duration::from_millis(1500)));
+
+        tracker.unmatched(&echo(1, start));
+        assert_eq!(rx.try_recv().unwrap().class, ReplyClass::Duplicate);
+
+        // 不是本目标发出的序号
+        tracker.unmatched(&echo(7, start));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_duplicates_before_answered_are_not_late() {
+        let target = PingTarget::new("localhost".to_string(), "127.0.0.1".parse().unwrap());
+        let (tx, mut rx) = mpsc::channel(8);
+        let mut tracker = ReplyTracker::new(target);
+        tracker.set_sink(Some(tx));
+
+        let start = Instant::now();
+        tracker.sent(3, start);
+
+        // 第一个回复交给等待方，等待方还没来得及处理时又到达两个相同的回复
+        tracker.delivered(3);
+        tracker.unmatched(&echo(3, start));
+        tracker.unmatched(&echo(3, start));
+        assert_eq!(rx.try_recv().unwrap().class, ReplyClass::Duplicate);
+        assert_eq!(rx.try_recv().unwrap().class, ReplyClass::Duplicate);
+        assert_eq!(tracker.answered(3), ReplyClass::OnTime);
+
+        // 交给等待方时它已超时放弃
+        tracker.sent(4, start);
+        tracker.delivered(4);
+        tracker.abandoned(&echo(4, start + Duration::from_secs(2)));
+        assert_eq!(rx.try_recv().unwrap().class, ReplyClass::Late);
+    }
+
+    #[test]
+    fn test_finished_probes_are_pruned() {
+        let target = PingTarget::new("localhost".to_string(), "127.0.0.1".parse().unwrap());
+        let (tx, mut rx) = mpsc::channel(8);
+        let mut tracker = ReplyTracker::new(target);
+        tracker.set_sink(Some(tx));
+
+        // 一直探测超过一轮序号，记录数不超过窗口的两倍
+        let start = Instant::now();
+        for i in 0..=u16::MAX as u32 + 10 {
+            let seq = i as u16;
+            tracker.sent(seq, start);
+            tracker.delivered(seq);
+        }
+        assert!(tracker.probes.len() <= 2 * TRACKED_WINDOW as usize + 1);
+
+        // 窗口之外的旧序号不再匹配，也就不会用错误的发送时间算出RTT
+        tracker.unmatched(&echo(20, start));
+        assert!(rx.try_recv().is_err());
+        tracker.unmatched(&echo(9, start));
+        assert_eq!(rx.try_recv().unwrap().class, ReplyClass::Duplicate);
+    }
+}
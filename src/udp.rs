@@ -0,0 +1,408 @@
+use rand::random;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::error::PingError;
+use crate::host::{PingResponse, PingTarget, Probe};
+use crate::icmp::{verify_payload, IcmpEchoReply, TimestampSource};
+use crate::pacer;
+use crate::pinger::{run_probes, RetryPolicy};
+use crate::socket::{Binding, Family};
+use crate::tracker::{ReplyTracker, SharedTracker};
+
+/// UDP探测头：4字节魔数 + 2字节会话标识 + 2字节序号 + 8字节发送时间戳（Unix纳秒）+ 8字节随机数
+pub const UDP_HEADER_LEN: usize = 24;
+
+/// 魔数，应答端只回显以它开头的数据报
+const MAGIC: [u8; 4] = *b"PUL\x01";
+
+/// 构造一个UDP探测，不足`size`的部分用递增字节填充
+pub fn encode_probe(session: u16, sequence: u16, nonce: u64, size: usize) -> Vec<u8> {
+    let mut packet: Vec<u8> = (0..size.max(UDP_HEADER_LEN))
+        .map(|i| (i % 256) as u8)
+        .collect();
+    let sent_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    packet[..4].copy_from_slice(&MAGIC);
+    packet[4..6].copy_from_slice(&session.to_be_bytes());
+    packet[6..8].copy_from_slice(&sequence.to_be_bytes());
+    packet[8..16].copy_from_slice(&sent_at.to_be_bytes());
+    packet[16..24].copy_from_slice(&nonce.to_be_bytes());
+    packet
+}
+
+/// 解析UDP探测头，返回(会话标识, 序号)
+pub fn decode_probe(data: &[u8]) -> Option<(u16, u16)> {
+    if data.len() < UDP_HEADER_LEN || data[..4] != MAGIC {
+        return None;
+    }
+    Some((
+        u16::from_be_bytes([data[4], data[5]]),
+        u16::from_be_bytes([data[6], data[7]]),
+    ))
+}
+
+/// 接收任务交给等待中的探测的结果
+enum UdpReply {
+    Echo {
+        payload: Vec<u8>,
+        received_at: Instant,
+    },
+    /// 连接的UDP socket收到ICMP端口不可达后recv返回ECONNREFUSED
+    PortUnreachable { received_at: Instant },
+}
+
+type Pending = Arc<Mutex<HashMap<u16, oneshot::Sender<UdpReply>>>>;
+
+/// UdpPinger向对端的`pingultra responder`发送带序号和时间戳的UDP数据报
+///
+/// 每个目标一个已连接的socket和一个接收任务，不需要root权限；
+/// 迟到、重复和乱序的回复与ICMP一样经ReplyTracker统计
+pub struct UdpPinger {
+    /// 目标主机的信息
+    target: PingTarget,
+    /// 对端应答端口
+    port: u16,
+    /// 数据报的大小
+    size: usize,
+    /// TTL值
+    ttl: u32,
+    /// 会话标识，区分同一端口上的多个pingultra
+    session: u16,
+    /// socket连接后的本地地址
+    source: Option<IpAddr>,
+    socket: Arc<UdpSocket>,
+    /// 等待中的探测，按序号索引
+    pending: Pending,
+    /// 记录每个探测的状态，识别迟到、重复和乱序的回复
+    tracker: SharedTracker,
+    receiver: JoinHandle<()>,
+}
+
+impl UdpPinger {
+    /// 创建一个新的UdpPinger，必须在tokio运行时中调用
+    ///
+    /// # 参数
+    ///
+    /// * `target`: 目标主机的信息
+    /// * `port`: 对端应答端口
+    /// * `size`: 数据报的大小，小于探测头时按探测头大小发送
+    /// * `ttl`: TTL值
+    /// * `binding`: 绑定的源地址和网卡
+    pub fn new(
+        target: PingTarget,
+        port: u16,
+        size: usize,
+        ttl: u32,
+        binding: &Binding,
+    ) -> Result<Self, PingError> {
+        let (domain, bind_addr): (Domain, SocketAddr) = match target.addr {
+            IpAddr::V4(_) => (Domain::IPV4, (Ipv4Addr::UNSPECIFIED, 0).into()),
+            IpAddr::V6(_) => (Domain::IPV6, (Ipv6Addr::UNSPECIFIED, 0).into()),
+        };
+        let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+        match target.addr {
+            IpAddr::V4(_) => socket.set_ttl(ttl)?,
+            IpAddr::V6(_) => socket.set_unicast_hops_v6(ttl)?,
+        }
+        socket.set_nonblocking(true)?;
+        binding.apply(&socket, Family::of(&target.addr))?;
+        if binding.source.is_none() {
+            socket.bind(&bind_addr.into())?;
+        }
+        // 连接后内核才会把ICMP端口不可达报告给这个socket
+        socket.connect(&SocketAddr::new(target.addr, port).into())?;
+        let socket = Arc::new(UdpSocket::from_std(socket.into())?);
+        let source = socket.local_addr().ok().map(|addr| addr.ip());
+
+        let session = random::<u16>();
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let mut tracker = ReplyTracker::new(target.clone());
+        tracker.set_probe(Probe::Udp { port });
+        let tracker = Arc::new(Mutex::new(tracker));
+
+        let receiver = tokio::spawn(receive_loop(
+            socket.clone(),
+            session,
+            pending.clone(),
+            tracker.clone(),
+        ));
+
+        Ok(Self {
+            target,
+            port,
+            size,
+            ttl,
+            session,
+            source,
+            socket,
+            pending,
+            tracker,
+            receiver,
+        })
+    }
+
+    fn failure(&self, seq: u16, error: PingError) -> PingResponse {
+        let mut response =
+            PingResponse::failure(self.target.clone(), seq, self.size, self.ttl as u8, error);
+        response.probe = Probe::Udp { port: self.port };
+        response.source = self.source;
+        response
+    }
+
+    /// 发送一个UDP探测并等待回显
+    ///
+    /// # 参数
+    ///
+    /// * `seq`: 探测的序号
+    /// * `timeout_ms`: 等待回显的超时时间（毫秒）
+    pub async fn ping_once(&self, seq: u16, timeout_ms: u64) -> PingResponse {
+        pacer::wait().await;
+        let packet = encode_probe(self.session, seq, random::<u64>(), self.size);
+
+        // 先登记再发送，避免回复在登记之前到达
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(seq, tx);
+        let start = Instant::now();
+        self.tracker.lock().unwrap().sent(seq, start);
+
+        if let Err(e) = self.socket.send(&packet).await {
+            self.pending.lock().unwrap().remove(&seq);
+            self.tracker.lock().unwrap().expired(seq);
+            // 前一个探测触发的端口不可达也可能在send时报告
+            if e.kind() == io::ErrorKind::ConnectionRefused {
+                return self.failure(seq, PingError::PortUnreachable(self.target.addr));
+            }
+            return self.failure(seq, PingError::SendError(e));
+        }
+
+        let mut response = match time::timeout(Duration::from_millis(timeout_ms), rx).await {
+            Ok(Ok(UdpReply::Echo {
+                payload,
+                received_at,
+            })) => {
+                let mut response = PingResponse::success(
+                    self.target.clone(),
+                    seq,
+                    received_at.saturating_duration_since(start),
+                    payload.len(),
+                    0,
+                );
+                response.payload_check = verify_payload(&packet, &payload);
+                response.class = self.tracker.lock().unwrap().answered(seq);
+                response
+            }
+            Ok(Ok(UdpReply::PortUnreachable { received_at })) => {
+                // 探测已有结果，之后到达的同序号数据报不能算作迟到的回复
+                self.tracker.lock().unwrap().expired(seq);
+                PingResponse::icmp_error(
+                    self.target.clone(),
+                    seq,
+                    received_at.saturating_duration_since(start),
+                    self.size,
+                    self.ttl as u8,
+                    PingError::PortUnreachable(self.target.addr),
+                )
+            }
+            _ => {
+                self.pending.lock().unwrap().remove(&seq);
+                self.tracker.lock().unwrap().expired(seq);
+                return self.failure(seq, PingError::Timeout);
+            }
+        };
+        response.probe = Probe::Udp { port: self.port };
+        response.source = self.source;
+        response
+    }
+
+    /// 发送多个UDP探测，参数与`Pinger::ping_multiple`相同
+    pub async fn ping_multiple(
+        &self,
+        count: u32,
+        period_ms: u64,
+        timeout_ms: u64,
+        retry: RetryPolicy,
+        tx: mpsc::Sender<PingResponse>,
+    ) -> Result<(), PingError> {
+        // 迟到和重复的回复由接收任务经跟踪器送入同一个通道
+        self.tracker.lock().unwrap().set_sink(Some(tx.clone()));
+
+        let result = run_probes(
+            count,
+            period_ms,
+            timeout_ms,
+            retry,
+            tx,
+            |seq, timeout_ms| self.ping_once(seq, timeout_ms),
+        )
+        .await;
+
+        // 释放通道的发送端，否则接收方无法结束
+        self.tracker.lock().unwrap().set_sink(None);
+
+        result
+    }
+}
+
+impl Drop for UdpPinger {
+    fn drop(&mut self) {
+        self.receiver.abort();
+    }
+}
+
+async fn receive_loop(
+    socket: Arc<UdpSocket>,
+    session: u16,
+    pending: Pending,
+    tracker: SharedTracker,
+) {
+    let mut recv_buffer = vec![0u8; 65536];
+
+    loop {
+        match socket.recv(&mut recv_buffer).await {
+            Ok(len) => {
+                let received_at = Instant::now();
+                let data = &recv_buffer[..len];
+                let sequence = match decode_probe(data) {
+                    Some((id, sequence)) if id == session => sequence,
+                    _ => continue,
+                };
+
+                let tx = pending.lock().unwrap().remove(&sequence);
+                match tx {
+                    Some(tx) => {
+                        // 先标记为已回复再交给等待方，之后到达的同序号回复才会被识别为重复
+                        tracker.lock().unwrap().delivered(sequence);
+                        let reply = UdpReply::Echo {
+                            payload: data.to_vec(),
+                            received_at,
+                        };
+                        // 等待方可能刚刚超时放弃，此时回复按迟到处理
+                        if let Err(UdpReply::Echo { payload, .. }) = tx.send(reply) {
+                            let echo = echo_reply(session, sequence, payload, received_at);
+                            tracker.lock().unwrap().abandoned(&echo);
+                        }
+                    }
+                    None => {
+                        let echo = echo_reply(session, sequence, data.to_vec(), received_at);
+                        tracker.lock().unwrap().unmatched(&echo);
+                    }
+                }
+            }
+            // 端口不可达无法对应到具体的序号，交给所有等待中的探测
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                let received_at = Instant::now();
+                for (_, tx) in pending.lock().unwrap().drain() {
+                    let _ = tx.send(UdpReply::PortUnreachable { received_at });
+                }
+            }
+            // 单个包的接收错误不影响后续接收
+            Err(_) => continue,
+        }
+    }
+}
+
+/// 把没有等待方的回显包装成Echo Reply，交给跟踪器识别迟到和重复
+fn echo_reply(
+    session: u16,
+    sequence: u16,
+    payload: Vec<u8>,
+    received_at: Instant,
+) -> IcmpEchoReply {
+    IcmpEchoReply {
+        identifier: session,
+        sequence,
+        ttl: 0,
+        size: payload.len(),
+        payload,
+        received_at,
+        timestamp_source: TimestampSource::Userspace,
+    }
+}
+
+/// 在指定端口上回显UDP探测，供另一端的`pingultra --udp`测量
+///
+/// 优先监听双栈的IPv6地址，不支持时只监听IPv4；不是探测格式的数据报被忽略
+pub async fn run_responder(port: u16, on_start: impl FnOnce(SocketAddr)) -> Result<(), PingError> {
+    let socket = match bind_dual_stack(port) {
+        Ok(socket) => socket,
+        Err(_) => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)).await?,
+    };
+    on_start(socket.local_addr()?);
+
+    let mut recv_buffer = vec![0u8; 65536];
+    loop {
+        let (len, from) = match socket.recv_from(&mut recv_buffer).await {
+            Ok(received) => received,
+            Err(_) => continue,
+        };
+        if decode_probe(&recv_buffer[..len]).is_some() {
+            let _ = socket.send_to(&recv_buffer[..len], from).await;
+        }
+    }
+}
+
+fn bind_dual_stack(port: u16) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_only_v6(false)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port).into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icmp::PayloadCheck;
+
+    #[test]
+    fn test_probe_round_trip() {
+        let packet = encode_probe(0xbeef, 42, 7, 64);
+        assert_eq!(packet.len(), 64);
+        assert_eq!(decode_probe(&packet), Some((0xbeef, 42)));
+
+        // 小于探测头时按探测头大小发送
+        assert_eq!(encode_probe(1, 2, 3, 8).len(), UDP_HEADER_LEN);
+
+        assert_eq!(decode_probe(&packet[..UDP_HEADER_LEN - 1]), None);
+        assert_eq!(decode_probe(&[0u8; UDP_HEADER_LEN]), None);
+    }
+
+    #[tokio::test]
+    async fn test_responder_echoes_and_closed_port_is_unreachable() {
+        let target = PingTarget::new("localhost".to_string(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        let (started_tx, started_rx) = oneshot::channel();
+        let responder = tokio::spawn(run_responder(0, move |addr| {
+            let _ = started_tx.send(addr.port());
+        }));
+        let port = started_rx.await.unwrap();
+        let pinger = UdpPinger::new(target.clone(), port, 64, 64, &Binding::default()).unwrap();
+        let response = pinger.ping_once(5, 1000).await;
+        assert!(response.is_success(), "{:?}", response.error);
+        assert_eq!(response.seq, 5);
+        assert_eq!(response.bytes, 64);
+        assert_eq!(response.payload_check, PayloadCheck::Intact);
+        responder.abort();
+
+        // 没有应答端的端口回复ICMP端口不可达
+        let closed = std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = closed.local_addr().unwrap().port();
+        drop(closed);
+        let pinger = UdpPinger::new(target, port, 64, 64, &Binding::default()).unwrap();
+        let response = pinger.ping_once(0, 1000).await;
+        assert!(matches!(
+            response.error,
+            Some(PingError::PortUnreachable(_))
+        ));
+    }
+}
@@ -14,6 +14,8 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use crate::monitor::DeviceInfo;
+use crate::mtr::MtrReport;
+use crate::output::{mtr_hop_host, mtr_hop_row, MTR_HEADER};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DeviceUIStatus {
@@ -998,3 +1000,71 @@ impl CharacterUI {
         parts.join(" | ")
     }
 }
+
+/// mtr子命令的终端界面，每轮探测后在原地刷新各跳的统计
+pub struct PathUI;
+
+impl PathUI {
+    pub fn enter() -> io::Result<Self> {
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+
+    pub fn render(&self, report: &MtrReport) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        execute!(
+            stdout,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 0)
+        )?;
+
+        let (width, height) = terminal::size()?;
+        let title = format!(
+            " PingUltra mtr: {} ({}) ",
+            report.target.name, report.target.addr
+        );
+        let padding = (width as usize).saturating_sub(title.chars().count()) / 2;
+        execute!(
+            stdout,
+            style::Print(" ".repeat(padding)),
+            style::PrintStyledContent(
+                style::style(title)
+                    .with(Color::Black)
+                    .on(Color::White)
+                    .bold()
+            ),
+            cursor::MoveTo(0, 1),
+            style::Print(MTR_HEADER),
+        )?;
+
+        let visible_rows = (height as usize).saturating_sub(3);
+        for (idx, hop) in report.hops.iter().take(visible_rows).enumerate() {
+            let host: String = mtr_hop_host(hop).chars().take(40).collect();
+            let loss = hop.stats.loss_percent();
+            let line = mtr_hop_row(hop, &host);
+            let color = if hop.stats.sent == 0 || loss == 0.0 {
+                Color::Reset
+            } else if loss < 100.0 {
+                Color::Yellow
+            } else {
+                Color::Red
+            };
+            execute!(
+                stdout,
+                cursor::MoveTo(0, (idx + 2) as u16),
+                style::PrintStyledContent(style::style(line).with(color))
+            )?;
+        }
+
+        execute!(
+            stdout,
+            cursor::MoveTo(0, height.saturating_sub(1)),
+            style::Print(format!("轮数: {} | 按Ctrl+C停止并输出报告", report.cycles))
+        )?;
+        stdout.flush()
+    }
+
+    pub fn leave(self) -> io::Result<()> {
+        execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen)
+    }
+}